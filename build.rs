@@ -4,6 +4,7 @@
 use std::process::Command;
 
 fn main() {
+    println!("cargo:rustc-check-cfg=cfg(external_doc)");
     if is_nightly().unwrap_or(false) {
         println!("cargo:rustc-cfg=external_doc");
     }
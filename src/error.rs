@@ -15,6 +15,7 @@ impl std::error::Error for Error {}
 impl Error {
     pub(crate) fn unsupported(s: &'static str) -> Self { Self(ZipError::UnsupportedArchive(s)) }
     pub(crate) fn io(io: std::io::Error) -> Self { Self(ZipError::Io(io)) }
+    pub(crate) fn not_found() -> Self { Self(ZipError::FileNotFound) }
 }
 
 #[cfg(feature = "vfs04")]
@@ -27,6 +28,7 @@ pub(crate) fn zip2vfs(path: &str, e: ZipError) -> vfs04::VfsError {
         ZipError::Io(e)                 => VfsError::IoError(e),
         ZipError::InvalidArchive(e)     => VfsError::IoError(IoError::new(InvalidData, e)),
         ZipError::UnsupportedArchive(e) => VfsError::IoError(IoError::new(InvalidData, e)),
+        #[allow(unreachable_patterns)] // ZipError isn't #[non_exhaustive] but might grow
         other                           => VfsError::Other { message: other.to_string() },
     }
 }
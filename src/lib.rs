@@ -3,7 +3,25 @@
 #![cfg_attr(external_doc, warn(missing_docs))]
 #![forbid(unsafe_code)]
 #![cfg_attr(not(feature = "vfs04"), allow(dead_code, unused_imports))]
+#![allow(clippy::identity_op)]                  // 1*KB
+#![allow(clippy::comparison_to_empty)]          // path == ""
+#![allow(clippy::if_same_then_else)]            // root dir vs explicit dir
+#![allow(clippy::upper_case_acronyms)]          // PZN
+#![allow(clippy::redundant_pattern_matching)]   // if let Some(_) = ...
+#![cfg_attr(test, allow(clippy::useless_format, clippy::bool_assert_comparison))]
+// The following lints suggest APIs newer than our MSRV
+#![allow(clippy::manual_range_contains)]        // 1.35: RangeInclusive::contains
+#![allow(clippy::manual_strip)]                 // 1.45: str::strip_prefix
+#![allow(clippy::mem_replace_with_default)]     // 1.40: std::mem::take
+#![allow(clippy::legacy_numeric_constants)]     // 1.43: u64::MAX
+#![allow(clippy::io_other_error)]               // 1.74: io::Error::other
+#![allow(clippy::manual_div_ceil)]              // 1.73: u64::div_ceil
+#![allow(clippy::seek_from_current)]            // 1.55: Seek::stream_position
+#![allow(clippy::unnecessary_map_or)]           // 1.70: Result::is_ok_and
+#![allow(clippy::manual_is_multiple_of)]        // 1.87: u64::is_multiple_of
 
 mod error; pub use error::*;
 #[path = "read/_read.rs"]   mod read;   pub use read::*;
 #[path = "write/_write.rs"] mod write;  pub use write::*;
+#[cfg(feature = "zip-aes")] mod winzip_aes; #[cfg(feature = "zip-aes")] pub(crate) use winzip_aes::*;
+#[cfg(feature = "zip-encodings")] pub use encoding_rs;
@@ -1,9 +1,25 @@
 #[cfg(feature = "vfs04")] mod vfs04;
 
 mod abs_seek_pos;   pub(crate) use abs_seek_pos::*;
+#[cfg(feature = "zip-aes")] mod aes_decrypt; #[cfg(feature = "zip-aes")] pub(crate) use aes_decrypt::*;
+#[cfg(feature = "zip-bzip2")] mod bzip2_blocks; #[cfg(feature = "zip-bzip2")] pub(crate) use bzip2_blocks::*;
+mod case_fold;      pub(crate) use case_fold::*;
+mod central_directory; pub(crate) use central_directory::*;
+mod cp437;          pub(crate) use cp437::*;
+mod crc32_check;    pub(crate) use crc32_check::*;
+mod extra_fields;   pub use extra_fields::*;
+mod forward_only;   pub(crate) use forward_only::*;
+mod index_cache;
+#[cfg(any(feature = "zip-deflate", feature = "zip-deflate64"))] mod inflate; #[cfg(any(feature = "zip-deflate", feature = "zip-deflate64"))] pub(crate) use inflate::*;
 mod into_clone_ral; pub use into_clone_ral::*;
+mod lazy_index;     pub(crate) use lazy_index::*;
+mod multi_volume;   pub use multi_volume::*;
+mod nfc;            pub(crate) use nfc::*;
 mod read_at_cursor; pub(crate) use read_at_cursor::*;
-mod read_at_ref;    pub(crate) use read_at_ref::*;
 mod read_range;     pub(crate) use read_range::*;
 mod seekless;       pub use seekless::*;
+mod zip_crypto;     pub(crate) use zip_crypto::*;
+mod zip_metadata;   pub use zip_metadata::*;
 mod zip_read_only;  pub use zip_read_only::*;
+mod zip_read_only_options; pub use zip_read_only_options::*;
+mod zip_warning;    pub use zip_warning::*;
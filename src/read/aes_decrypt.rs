@@ -0,0 +1,91 @@
+use super::AbsSeekPos;
+use crate::{AesKeys, AesMac, aes_salt_size, AES_MAC_SIZE, AES_VERIFIER_SIZE};
+
+use hmac::Mac;
+
+use std::io::{self, Read, Seek, SeekFrom};
+
+
+
+/// A seekable WinZip AES decryptor.
+///
+/// AES-CTR decrypts any position directly, so seeking is cheap.  Like [Crc32Check](super::Crc32Check), the HMAC-SHA1
+/// authentication code is only verified once the encrypted data has been read contiguously from the start through to
+/// the end, failing that final read with [InvalidData](io::ErrorKind::InvalidData) on a mismatch.
+pub(crate) struct AesDecrypt<R> {
+    inner:      R,
+    keys:       AesKeys,
+    data_start: u64,            // salt + verifier size
+    length:     u64,            // encrypted data length (excludes salt, verifier, and authentication code)
+    pos:        Option<u64>,    // inner position relative to data_start, if known
+    target:     u64,            // logical seek position
+    mac:        AesMac,
+    expected:   [u8; AES_MAC_SIZE as usize],
+    hashed:     u64,            // [0 .. hashed) has been fed to mac
+    verified:   bool,
+}
+
+impl<R: Read + Seek> AesDecrypt<R> {
+    /// `inner` is the entire (encrypted) file entry data: salt, password verifier, encrypted data, authentication code.
+    /// An incorrect password results in a [PermissionDenied](io::ErrorKind::PermissionDenied) error.
+    pub fn new(mut inner: R, password: &[u8], strength: u8, inner_length: u64) -> io::Result<Self> {
+        let data_start = aes_salt_size(strength)? + AES_VERIFIER_SIZE;
+        if inner_length < data_start + AES_MAC_SIZE { return Err(io::Error::new(io::ErrorKind::InvalidData, "AES encrypted file entry too short")) }
+        let length = inner_length - data_start - AES_MAC_SIZE;
+
+        let mut header = vec![0u8; data_start as usize];
+        inner.seek(SeekFrom::Start(0))?;
+        inner.read_exact(&mut header[..])?;
+        let (salt, verifier) = header.split_at(data_start as usize - AES_VERIFIER_SIZE as usize);
+        let keys = AesKeys::derive(password, salt, strength)?;
+        if verifier != keys.verifier() { return Err(io::Error::new(io::ErrorKind::PermissionDenied, "invalid password for encrypted file entry")) }
+
+        let mut expected = [0u8; AES_MAC_SIZE as usize];
+        inner.seek(SeekFrom::Start(data_start + length))?;
+        inner.read_exact(&mut expected[..])?;
+
+        let mac = keys.mac();
+        Ok(Self { inner, keys, data_start, length, pos: None, target: 0, mac, expected, hashed: 0, verified: false })
+    }
+}
+
+impl<R: Read + Seek> Read for AesDecrypt<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let pos = self.target;
+        if self.pos != Some(pos) {
+            self.inner.seek(SeekFrom::Start(self.data_start + pos))?;
+            self.pos = Some(pos);
+        }
+
+        let n = (self.length.saturating_sub(pos)).min(buf.len() as u64) as usize;
+        let read = self.inner.read(&mut buf[..n])?;
+        if read == 0 && n != 0 { return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "AES encrypted file entry ended before its expected length, is the archive truncated?")) }
+        let end = pos + read as u64;
+
+        if pos <= self.hashed && self.hashed < end {
+            let skip = (self.hashed - pos) as usize; // < read
+            self.mac.update(&buf[skip..read]);
+            self.hashed = end;
+        }
+        self.keys.apply_keystream(pos, &mut buf[..read]);
+        self.pos    = Some(end);
+        self.target = end;
+
+        if self.hashed >= self.length && !self.verified {
+            let actual = self.mac.clone().finalize().into_bytes();
+            if actual[..self.expected.len()] != self.expected[..] {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "AES authentication code mismatch"));
+            }
+            self.verified = true;
+        }
+
+        Ok(read)
+    }
+}
+
+impl<R: Read + Seek> Seek for AesDecrypt<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.target = AbsSeekPos(self.target).offset_bounded(pos, self.length)?.0;
+        Ok(self.target)
+    }
+}
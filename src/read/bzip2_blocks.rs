@@ -0,0 +1,233 @@
+use super::AbsSeekPos;
+
+use bzip2::read::BzDecoder;
+
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+use std::sync::{Arc, Mutex};
+
+
+
+/// A seekable bzip2 decoder.
+///
+/// bzip2 streams are a series of independently compressed blocks, each starting with a 48-bit magic number at an
+/// arbitrary bit offset.  The first read scans the compressed data for these magic numbers, recording block offsets in a
+/// (possibly shared) [Bzip2Index].  Any single block can then be decompressed by wrapping it in a synthetic single-block
+/// bzip2 stream.  Decompressed block sizes are recorded lazily as blocks are decompressed, after which seeking only needs
+/// to decompress the block containing the target.
+pub(crate) struct Bzip2Blocks<R> {
+    input:      R,
+    index:      Arc<Mutex<Bzip2Index>>,
+    length:     u64, // expected decompressed length
+    target:     u64, // logical seek position
+    current:    Option<CurrentBlock>,
+}
+
+struct CurrentBlock {
+    block:      usize,
+    out_start:  u64,
+    pos:        u64, // decompressed bytes read from this block so far
+    decoder:    BzDecoder<Cursor<Vec<u8>>>,
+}
+
+impl<R: Read + Seek> Bzip2Blocks<R> {
+    pub fn new(input: R, length: u64, index: Arc<Mutex<Bzip2Index>>) -> Self {
+        Self { input, index, length, target: 0, current: None }
+    }
+
+    fn pos(&self) -> Option<u64> { self.current.as_ref().map(|c| c.out_start + c.pos) }
+
+    /// Position `self.current` at `self.target`, or at `None` if the target is past EOF.
+    fn reposition(&mut self) -> io::Result<()> {
+        if self.pos() != Some(self.target) {
+            let (block, out_start) = {
+                let mut index = self.index.lock().unwrap();
+                if !index.scanned { index.scan(&mut self.input)?; }
+                index.block_for(self.target)
+            };
+
+            let reuse = match self.current.as_ref() {
+                Some(c) => c.block >= block && c.out_start + c.pos < self.target,
+                None    => false,
+            };
+            if !reuse { self.open_block(block, out_start)?; }
+        }
+
+        let mut scratch = [0u8; 8 * 1024];
+        while let Some(pos) = self.pos() {
+            if pos >= self.target { break }
+            let n = ((self.target - pos).min(scratch.len() as u64)) as usize;
+            let c = self.current.as_mut().unwrap();
+            let read = c.decoder.read(&mut scratch[..n])?;
+            c.pos += read as u64;
+            if read == 0 { self.finish_block()?; }
+        }
+        Ok(())
+    }
+
+    fn open_block(&mut self, block: usize, out_start: u64) -> io::Result<()> {
+        let range = {
+            let index = self.index.lock().unwrap();
+            index.blocks.get(block).map(|b| (b.bit_start, b.bit_end))
+        };
+        self.current = match range {
+            None => None,
+            Some((bit_start, bit_end)) => {
+                let stream = single_block_stream(&mut self.input, bit_start, bit_end)?;
+                Some(CurrentBlock { block, out_start, pos: 0, decoder: BzDecoder::new(Cursor::new(stream)) })
+            },
+        };
+        Ok(())
+    }
+
+    fn finish_block(&mut self) -> io::Result<()> {
+        let (block, out_start, pos) = match self.current.as_ref() {
+            None    => return Ok(()),
+            Some(c) => (c.block, c.out_start, c.pos),
+        };
+        self.index.lock().unwrap().blocks[block].out_len = Some(pos);
+        self.open_block(block + 1, out_start + pos)
+    }
+}
+
+impl<R: Read + Seek> Read for Bzip2Blocks<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() { return Ok(0) }
+        self.reposition()?;
+        while let Some(c) = self.current.as_mut() {
+            let read = c.decoder.read(buf)?;
+            if read != 0 {
+                c.pos += read as u64;
+                self.target += read as u64;
+                return Ok(read);
+            }
+            self.finish_block()?;
+        }
+        Ok(0)
+    }
+}
+
+impl<R: Read + Seek> Seek for Bzip2Blocks<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.target = AbsSeekPos(self.target).offset_bounded(pos, self.length)?.0;
+        Ok(self.target)
+    }
+}
+
+
+
+/// Block offsets (and lazily, decompressed block sizes) shared between every [Bzip2Blocks] reading the same bzip2 stream,
+/// dropped once they've all been closed
+#[derive(Default)]
+pub(crate) struct Bzip2Index {
+    scanned:    bool,
+    blocks:     Vec<Bzip2Block>,
+}
+
+struct Bzip2Block {
+    bit_start:  u64,            // offset of the block magic
+    bit_end:    u64,            // offset of the next block or end-of-stream magic
+    out_len:    Option<u64>,    // decompressed size, once known
+}
+
+const BLOCK_MAGIC   : u64 = 0x3141_5926_5359; // BCD pi
+const EOS_MAGIC     : u64 = 0x1772_4538_5090; // BCD sqrt(pi)
+const MAGIC_MASK    : u64 = 0xFFFF_FFFF_FFFF;
+
+impl Bzip2Index {
+    fn scan(&mut self, input: &mut (impl Read + Seek)) -> io::Result<()> {
+        input.seek(SeekFrom::Start(0))?;
+        self.blocks.clear();
+        let mut reg = 0u64;
+        let mut bit_pos = 0u64; // bit offset just past reg's lowest bit
+        let mut buf = vec![0u8; 64 * 1024];
+        let mut open_block = None;
+
+        loop {
+            let read = input.read(&mut buf[..])?;
+            if read == 0 { break }
+            for &byte in buf[..read].iter() {
+                for shift in (0..8).rev() {
+                    reg = (reg << 1 | (byte >> shift) as u64 & 1) & MAGIC_MASK;
+                    bit_pos += 1;
+                    if bit_pos < 48 || (reg != BLOCK_MAGIC && reg != EOS_MAGIC) { continue }
+
+                    let magic_start = bit_pos - 48;
+                    if let Some(bit_start) = open_block.take() {
+                        self.blocks.push(Bzip2Block { bit_start, bit_end: magic_start, out_len: None });
+                    }
+                    if reg == BLOCK_MAGIC { open_block = Some(magic_start); }
+                }
+            }
+        }
+
+        if open_block.is_some() { return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "bzip2 stream ended unexpectedly")) }
+        self.scanned = true;
+        Ok(())
+    }
+
+    /// Returns the index and decompressed offset of the block to start decompressing at to reach `out_pos`.
+    /// This is either the block containing `out_pos`, or the first block of unknown decompressed size.
+    fn block_for(&self, out_pos: u64) -> (usize, u64) {
+        let mut out_start = 0;
+        for (i, block) in self.blocks.iter().enumerate() {
+            match block.out_len {
+                Some(len) if out_start + len <= out_pos => out_start += len,
+                _ => return (i, out_start),
+            }
+        }
+        (self.blocks.len(), out_start)
+    }
+}
+
+/// Read the bits `bit_start .. bit_end` (a single block) and wrap them in a standalone bzip2 stream.
+fn single_block_stream(input: &mut (impl Read + Seek), bit_start: u64, bit_end: u64) -> io::Result<Vec<u8>> {
+    let byte_start = bit_start / 8;
+    let byte_end = (bit_end + 7) / 8;
+    input.seek(SeekFrom::Start(byte_start))?;
+    let mut raw = Vec::new();
+    input.take(byte_end - byte_start).read_to_end(&mut raw)?;
+
+    let shift = (bit_start % 8) as u32;
+    let raw_byte = |i: usize| raw.get(i).cloned().unwrap_or(0);
+    let byte = |i: usize| if shift == 0 { raw_byte(i) } else { raw_byte(i) << shift | raw_byte(i+1) >> (8 - shift) };
+
+    let nbits = bit_end - bit_start;
+    if nbits < 80 { return Err(io::Error::new(io::ErrorKind::InvalidData, "bzip2 block too short")) }
+    let block_crc = (6..10).fold(0, |crc, i| crc << 8 | byte(i) as u64); // bits 48 .. 80
+
+    let mut w = BitWriter::default();
+    w.out.extend_from_slice(b"BZh9"); // largest block size - the real level might be smaller, which is fine
+    let full_bytes = (nbits / 8) as usize;
+    w.out.extend((0..full_bytes).map(byte));
+    if nbits % 8 != 0 { w.push((byte(full_bytes) >> (8 - nbits % 8)) as u64, (nbits % 8) as u32); } // u8 >> 8 would overflow
+    w.push(EOS_MAGIC, 48);
+    w.push(block_crc, 32); // combined CRC of a single block stream == rotl(0, 1) ^ block_crc
+    Ok(w.finish())
+}
+
+#[derive(Default)]
+struct BitWriter {
+    out:    Vec<u8>,
+    acc:    u8,
+    nbits:  u32,
+}
+
+impl BitWriter {
+    /// Append the lowest `bits` of `value`, MSB first
+    fn push(&mut self, value: u64, bits: u32) {
+        for shift in (0..bits).rev() {
+            self.acc = self.acc << 1 | (value >> shift) as u8 & 1;
+            self.nbits += 1;
+            if self.nbits == 8 {
+                self.out.push(self.acc);
+                self.acc = 0;
+                self.nbits = 0;
+            }
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.nbits > 0 { self.out.push(self.acc << (8 - self.nbits)); }
+        self.out
+    }
+}
@@ -0,0 +1,34 @@
+/// Unicode simple case folding (approximately [CaseFolding.txt]'s `C` + `S` mappings), for case-insensitive lookups.
+///
+/// Characters are lowercased one at a time, keeping those whose lowercase form isn't a single character (like `İ`),
+/// plus the handful of characters whose case fold differs from their lowercase form (like `ς` and `ſ`).
+/// The results are only meaningful for comparing against other folded strings.
+///
+/// [CaseFolding.txt]:  https://www.unicode.org/Public/UCD/latest/ucd/CaseFolding.txt
+pub(crate) fn fold_case(s: &str) -> String {
+    s.chars().map(fold_char).collect()
+}
+
+fn fold_char(ch: char) -> char {
+    let mut lower = ch.to_lowercase();
+    let ch = match (lower.next(), lower.next()) {
+        (Some(lower), None) => lower,
+        _                   => ch,
+    };
+    match ch {
+        '\u{00B5}' => '\u{03BC}', // MICRO SIGN -> GREEK SMALL LETTER MU
+        '\u{017F}' => 's',        // LATIN SMALL LETTER LONG S
+        '\u{0345}' => '\u{03B9}', // COMBINING GREEK YPOGEGRAMMENI -> GREEK SMALL LETTER IOTA
+        '\u{03C2}' => '\u{03C3}', // GREEK SMALL LETTER FINAL SIGMA -> SIGMA
+        '\u{03D0}' => '\u{03B2}', // GREEK BETA SYMBOL
+        '\u{03D1}' => '\u{03B8}', // GREEK THETA SYMBOL
+        '\u{03D5}' => '\u{03C6}', // GREEK PHI SYMBOL
+        '\u{03D6}' => '\u{03C0}', // GREEK PI SYMBOL
+        '\u{03F0}' => '\u{03BA}', // GREEK KAPPA SYMBOL
+        '\u{03F1}' => '\u{03C1}', // GREEK RHO SYMBOL
+        '\u{03F5}' => '\u{03B5}', // GREEK LUNATE EPSILON SYMBOL
+        '\u{1E9B}' => '\u{1E61}', // LATIN SMALL LETTER LONG S WITH DOT ABOVE
+        '\u{1FBE}' => '\u{03B9}', // GREEK PROSGEGRAMMENI
+        ch         => ch,
+    }
+}
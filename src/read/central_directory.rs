@@ -0,0 +1,223 @@
+use read_write_at::ReadAt;
+
+use std::convert::*;
+use std::io;
+
+
+
+const LOCAL_FILE_HEADER_SIGNATURE      : u32 = 0x04034b50;
+const EOCD_SIGNATURE                    : u32 = 0x06054b50;
+const ZIP64_EOCD_SIGNATURE              : u32 = 0x06064b50;
+const ZIP64_EOCD_LOCATOR_SIGNATURE      : u32 = 0x07064b50;
+const CENTRAL_DIRECTORY_HEADER_SIGNATURE: u32 = 0x02014b50;
+
+const EOCD_SIZE                 : u64 = 22;
+const ZIP64_EOCD_LOCATOR_SIZE   : u64 = 20;
+const ZIP64_EOCD_SIZE           : u64 = 56; // excluding any extensible data
+const CENTRAL_HEADER_SIZE       : usize = 46;
+const LOCAL_HEADER_SIZE         : usize = 30;
+
+/// The raw central directory of a zip archive, read in one go
+pub(crate) struct CentralDirectory {
+    pub bytes:          Vec<u8>,
+    pub entries:        u64,
+    pub archive_offset: u64, // the length of any data prepended to the archive
+    pub disk_offsets:   Vec<u64>, // the offset of each disk's volume, only for spanned archives
+    pub comment:        Vec<u8>, // the archive comment from the end of central directory record
+}
+
+/// A central directory file header.  Sizes and offsets have any Zip64 extended information applied, and `header_offset`
+/// accounts for data prepended to the archive, or for the entry's disk within spanned archives.
+pub(crate) struct CentralRecord<'cd> {
+    pub version_made_by:        u16,
+    pub flags:                  u16,
+    pub compression:            u16,
+    pub dos_time:               u16,
+    pub dos_date:               u16,
+    pub crc32:                  u32,
+    pub compressed:             u64,
+    pub uncompressed:           u64,
+    pub external_attributes:    u32,
+    pub header_offset:          u64,
+    pub disk:                   u32, // the disk the entry's local header is on
+    pub name:                   &'cd [u8],
+    pub extra:                  &'cd [u8],
+    pub comment:                &'cd [u8],
+}
+
+/// The fixed size portion of a local file header
+pub(crate) struct LocalHeader {
+    pub flags:      u16,
+    pub dos_time:   u16,
+    pub name_len:   u16,
+    pub extra_len:  u16,
+}
+
+impl LocalHeader {
+    /// The size of the local header including the name and extra field: the offset of the file data from the header
+    pub fn size(&self) -> u64 { LOCAL_HEADER_SIZE as u64 + self.name_len as u64 + self.extra_len as u64 }
+}
+
+/// Read the central directory of the zip archive `io` (of length `len`) in a few large reads.
+///
+/// Spanned archives (whose end of central directory record isn't on disk 0) resolve disk numbers via `volumes`, the
+/// offset of each disk's volume within `io` (see [IntoCloneReadAtLen::volume_offsets](super::IntoCloneReadAtLen::volume_offsets).)
+pub(crate) fn read_central_directory(io: &impl ReadAt, len: u64, volumes: &[u64]) -> io::Result<CentralDirectory> {
+    let (eocd_pos, eocd, comment) = find_eocd(io, len)?;
+    let mut disk        = u16le(&eocd[4..]) as u32;
+    let mut cd_disk     = u16le(&eocd[6..]) as u32;
+    let mut entries     = u16le(&eocd[10..]) as u64;
+    let mut cd_size     = u32le(&eocd[12..]) as u64;
+    let mut cd_offset   = u32le(&eocd[16..]) as u64;
+    let mut cd_end      = eocd_pos;
+    let disk_start = |disk: u32| if disk == 0 { Ok(0) } else { volumes.get(disk as usize).cloned().ok_or_else(|| invalid_data("invalid disk number")) };
+
+    if eocd_pos >= ZIP64_EOCD_LOCATOR_SIZE {
+        let mut locator = [0u8; ZIP64_EOCD_LOCATOR_SIZE as usize];
+        io.read_exact_at(&mut locator[..], eocd_pos - ZIP64_EOCD_LOCATOR_SIZE)?;
+        if u32le(&locator[..]) == ZIP64_EOCD_LOCATOR_SIGNATURE {
+            // The locator's offset doesn't account for prepended data, but the record usually immediately precedes the locator
+            let mut zip64_eocd = [0u8; ZIP64_EOCD_SIZE as usize];
+            let recorded_pos = disk_start(u32le(&locator[4..]))?.checked_add(u64le(&locator[8..])).ok_or_else(|| invalid_data("invalid zip64 end of central directory locator"))?;
+            let adjacent_pos = (eocd_pos - ZIP64_EOCD_LOCATOR_SIZE).checked_sub(ZIP64_EOCD_SIZE);
+            let zip64_eocd_pos = match adjacent_pos {
+                Some(pos) if pos != recorded_pos && io.read_exact_at(&mut zip64_eocd[..], pos).is_ok() && u32le(&zip64_eocd[..]) == ZIP64_EOCD_SIGNATURE => pos,
+                _ => { io.read_exact_at(&mut zip64_eocd[..], recorded_pos)?; recorded_pos },
+            };
+            if u32le(&zip64_eocd[..]) != ZIP64_EOCD_SIGNATURE { return Err(invalid_data("invalid zip64 end of central directory record")) }
+            disk        = u32le(&zip64_eocd[16..]);
+            cd_disk     = u32le(&zip64_eocd[20..]);
+            entries     = u64le(&zip64_eocd[32..]);
+            cd_size     = u64le(&zip64_eocd[40..]);
+            cd_offset   = u64le(&zip64_eocd[48..]);
+            cd_end      = zip64_eocd_pos;
+        }
+    }
+    let spanned = disk != 0; // only after applying Zip64 values: saturated (0xFFFF) disk numbers just mean "see the Zip64 record"
+    let all_volumes = if spanned { volumes.len() as u64 == disk as u64 + 1 } else { disk == 0 && cd_disk == 0 };
+    if !all_volumes { return Err(invalid_data("vfs-zip requires every volume of multi-disk zip archives (see MultiVolume)")) }
+
+    // Archives with prepended data (self extracting executables etc.) have offsets relative to the start of the zip.
+    // Spanned archives instead have offsets relative to the start of each disk's volume.
+    let archive_offset = if spanned { 0 } else { cd_end.checked_sub(cd_size).and_then(|o| o.checked_sub(cd_offset)).ok_or_else(|| invalid_data("invalid central directory offset"))? };
+    let cd_pos = archive_offset.checked_add(disk_start(cd_disk)?).and_then(|p| p.checked_add(cd_offset)).ok_or_else(|| invalid_data("invalid central directory offset"))?;
+    if cd_pos.checked_add(cd_size).map_or(true, |end| end > cd_end) { return Err(invalid_data("invalid central directory size")) } // before allocating it
+    let cd_size : usize = cd_size.try_into().map_err(|_| invalid_data("central directory too large"))?;
+    let mut cd = vec![0u8; cd_size];
+    io.read_exact_at(&mut cd[..], cd_pos)?;
+
+    let disk_offsets = if spanned { volumes.to_vec() } else { Vec::new() };
+    Ok(CentralDirectory { bytes: cd, entries, archive_offset, disk_offsets, comment })
+}
+
+impl CentralDirectory {
+    /// Iterate over every record as (offset of the record within [bytes](Self::bytes), record), in central directory order.
+    pub fn records(&self) -> impl Iterator<Item = io::Result<(usize, CentralRecord<'_>)>> {
+        let mut offset = 0;
+        (0 .. self.entries).map(move |_| {
+            let (record, size) = self.record_at(offset)?;
+            let result = (offset, record);
+            offset += size;
+            Ok(result)
+        })
+    }
+
+    /// Parse the record at `offset` within [bytes](Self::bytes), returning it and its size.
+    pub fn record_at(&self, offset: usize) -> io::Result<(CentralRecord<'_>, usize)> {
+        let rest = self.bytes.get(offset..).unwrap_or(&[]);
+        if rest.len() < CENTRAL_HEADER_SIZE || u32le(rest) != CENTRAL_DIRECTORY_HEADER_SIGNATURE { return Err(invalid_data("invalid central directory header")) }
+        let name_len    = u16le(&rest[28..]) as usize;
+        let name_extra  = name_len + u16le(&rest[30..]) as usize;
+        let variable    = name_extra + u16le(&rest[32..]) as usize; // name, extra, comment
+        let header      = rest.get(.. CENTRAL_HEADER_SIZE + variable).ok_or_else(|| invalid_data("central directory header overflows central directory"))?;
+        let mut record  = CentralRecord {
+            version_made_by:        u16le(&header[4..]),
+            flags:                  u16le(&header[8..]),
+            compression:            u16le(&header[10..]),
+            dos_time:               u16le(&header[12..]),
+            dos_date:               u16le(&header[14..]),
+            crc32:                  u32le(&header[16..]),
+            compressed:             u32le(&header[20..]) as u64,
+            uncompressed:           u32le(&header[24..]) as u64,
+            external_attributes:    u32le(&header[38..]),
+            header_offset:          u32le(&header[42..]) as u64,
+            disk:                   u16le(&header[34..]) as u32,
+            name:                   &header[CENTRAL_HEADER_SIZE .. CENTRAL_HEADER_SIZE + name_len],
+            extra:                  &header[CENTRAL_HEADER_SIZE + name_len .. CENTRAL_HEADER_SIZE + name_extra],
+            comment:                &header[CENTRAL_HEADER_SIZE + name_extra ..],
+        };
+        record.apply_zip64();
+        let disk_offset = if self.disk_offsets.is_empty() { 0 } else { *self.disk_offsets.get(record.disk as usize).ok_or_else(|| invalid_data("central directory header has an invalid disk number"))? };
+        record.header_offset = record.header_offset.checked_add(self.archive_offset).and_then(|o| o.checked_add(disk_offset)).ok_or_else(|| invalid_data("central directory header has an invalid local header offset"))?;
+        Ok((record, header.len()))
+    }
+}
+
+impl<'cd> CentralRecord<'cd> {
+    /// Replace saturated (0xFFFFFFFF) sizes and offsets, and saturated (0xFFFF) disk numbers, with their values from the
+    /// Zip64 extended information extra field (0x0001), which only contains the saturated fields, in order.  Like `zip`,
+    /// missing values are left saturated.
+    fn apply_zip64(&mut self) {
+        const SATURATED : u64 = 0xFFFF_FFFF;
+        let mut field = match super::extra_field(self.extra, 0x0001) { Some(f) => f, None => return };
+        for value in [&mut self.uncompressed, &mut self.compressed, &mut self.header_offset].iter_mut() {
+            if **value != SATURATED { continue }
+            if field.len() < 8 { return }
+            **value = u64le(field);
+            field = &field[8..];
+        }
+        if self.disk == 0xFFFF && field.len() >= 4 { self.disk = u32le(field); }
+    }
+}
+
+/// Read the local file header at `header_offset`
+pub(crate) fn read_local_header(io: &impl ReadAt, header_offset: u64) -> io::Result<LocalHeader> {
+    let mut header = [0u8; LOCAL_HEADER_SIZE];
+    io.read_exact_at(&mut header[..], header_offset)?;
+    if u32le(&header[..]) != LOCAL_FILE_HEADER_SIGNATURE { return Err(invalid_data("invalid local file header")) }
+    Ok(LocalHeader {
+        flags:      u16le(&header[6..]),
+        dos_time:   u16le(&header[10..]),
+        name_len:   u16le(&header[26..]),
+        extra_len:  u16le(&header[28..]),
+    })
+}
+
+/// Read the bytes identifying the archive's current central directory, for validating index caches: the end of central
+/// directory record and archive comment, preceded by the Zip64 end of central directory record and locator (if present.)
+pub(crate) fn read_eocd_bytes(io: &impl ReadAt, len: u64) -> io::Result<Vec<u8>> {
+    let (eocd_pos, _, _) = find_eocd(io, len)?;
+    let start = eocd_pos.saturating_sub(ZIP64_EOCD_LOCATOR_SIZE + ZIP64_EOCD_SIZE);
+    let mut bytes = vec![0u8; (len - start) as usize];
+    io.read_exact_at(&mut bytes[..], start)?;
+    Ok(bytes)
+}
+
+/// Find the end of central directory record (and the archive comment following it) by scanning backwards past any archive comment.
+/// Comments can contain things that look like signatures, so prefer the record whose comment ends exactly at the end of the
+/// archive, falling back on the last record whose comment fits (tolerating trailing garbage.)
+fn find_eocd(io: &impl ReadAt, len: u64) -> io::Result<(u64, [u8; EOCD_SIZE as usize], Vec<u8>)> {
+    if len < EOCD_SIZE { return Err(invalid_data("too small to be a zip archive")) }
+    let search_start = len.saturating_sub(EOCD_SIZE + 0xFFFF);
+    let mut tail = vec![0u8; (len - search_start) as usize];
+    io.read_exact_at(&mut tail[..], search_start)?;
+
+    let mut fallback = None;
+    for i in (0 ..= tail.len() - EOCD_SIZE as usize).rev() {
+        if u32le(&tail[i..]) != EOCD_SIGNATURE { continue }
+        let comment_start = i + EOCD_SIZE as usize;
+        let comment_end = comment_start + u16le(&tail[i + 20..]) as usize;
+        if comment_end == tail.len() { fallback = Some(i); break }
+        if comment_end < tail.len() && fallback.is_none() { fallback = Some(i); } // otherwise a signature within a comment, or truncated
+    }
+    let i = fallback.ok_or_else(|| invalid_data("unable to find end of central directory record"))?;
+    let mut eocd = [0u8; EOCD_SIZE as usize];
+    eocd.copy_from_slice(&tail[i .. i + EOCD_SIZE as usize]);
+    let comment = tail[i + EOCD_SIZE as usize ..][..u16le(&eocd[20..]) as usize].to_vec();
+    Ok((search_start + i as u64, eocd, comment))
+}
+
+fn u16le(b: &[u8]) -> u16 { u16::from_le_bytes([b[0], b[1]]) }
+fn u32le(b: &[u8]) -> u32 { u32::from_le_bytes([b[0], b[1], b[2], b[3]]) }
+fn u64le(b: &[u8]) -> u64 { let mut a = [0u8; 8]; a.copy_from_slice(&b[..8]); u64::from_le_bytes(a) }
+fn invalid_data(message: &'static str) -> io::Error { io::Error::new(io::ErrorKind::InvalidData, message) }
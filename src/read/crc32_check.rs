@@ -0,0 +1,66 @@
+use std::io::{self, Read, Seek, SeekFrom};
+
+
+
+/// Adapt [Read] + [Seek] to verify a CRC32 once the whole stream has been read.
+///
+/// Only data read contiguously from the start of the stream is hashed.  Seeking backwards is fine, as is re-reading
+/// already hashed data, but skipping ahead past unhashed data will leave the stream unverified until said data is read.
+pub(crate) struct Crc32Check<R> {
+    inner:      R,
+    expected:   u32,
+    length:     u64,
+    pos:        u64,
+    hashed:     u64, // [0 .. hashed) has been fed to hasher
+    hasher:     crc32fast::Hasher,
+    verified:   bool,
+}
+
+impl<R> Crc32Check<R> {
+    pub fn new(inner: R, expected: u32, length: u64) -> Self {
+        Self {
+            inner,
+            expected,
+            length,
+            pos:        0,
+            hashed:     0,
+            hasher:     crc32fast::Hasher::new(),
+            verified:   false,
+        }
+    }
+}
+
+impl<R: Read> Read for Crc32Check<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        let end = self.pos + read as u64;
+
+        if self.pos <= self.hashed && self.hashed < end {
+            let skip = (self.hashed - self.pos) as usize; // < read
+            self.hasher.update(&buf[skip..read]);
+            self.hashed = end;
+        }
+        self.pos = end;
+
+        if read == 0 && !buf.is_empty() && self.pos < self.length {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "file entry ended before its expected length, is the archive truncated?"));
+        }
+
+        if self.hashed >= self.length && !self.verified {
+            let actual = self.hasher.clone().finalize();
+            if actual != self.expected {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("CRC32 mismatch: expected {:08x}, got {:08x}", self.expected, actual)));
+            }
+            self.verified = true;
+        }
+
+        Ok(read)
+    }
+}
+
+impl<R: Seek> Seek for Crc32Check<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.pos = self.inner.seek(pos)?;
+        Ok(self.pos)
+    }
+}
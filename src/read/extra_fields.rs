@@ -0,0 +1,40 @@
+/// An iterator over the `(header_id, data)` pairs of a zip entry's extra fields, as returned by
+/// [ZipMetadata::extra_fields](crate::ZipMetadata::extra_fields) and
+/// [ZipMetadata::local_extra_fields](crate::ZipMetadata::local_extra_fields).
+///
+/// Iteration stops early at a truncated field.
+///
+/// ```rust
+/// # use vfs_zip::*;
+/// # fn vendor_id(metadata: &ZipMetadata) -> Option<&[u8]> {
+/// metadata.extra_fields().find(|&(id, _)| id == 0xCAFE).map(|(_, data)| data)
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct ExtraFields<'a> {
+    remaining: &'a [u8],
+}
+
+impl<'a> ExtraFields<'a> {
+    pub(crate) fn new(extra: &'a [u8]) -> Self { Self { remaining: extra } }
+}
+
+impl<'a> Iterator for ExtraFields<'a> {
+    type Item = (u16, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let extra = self.remaining;
+        if extra.len() < 4 { self.remaining = &[]; return None }
+        let id      = u16::from_le_bytes([extra[0], extra[1]]);
+        let size    = u16::from_le_bytes([extra[2], extra[3]]) as usize;
+        match extra[4..].get(..size) {
+            Some(data) => { self.remaining = &extra[4+size..]; Some((id, data)) },
+            None => { self.remaining = &[]; None },
+        }
+    }
+}
+
+/// Find the data of the first extra field with the header ID `id`
+pub(crate) fn extra_field(extra: &[u8], id: u16) -> Option<&[u8]> {
+    ExtraFields::new(extra).find(|&(field_id, _)| field_id == id).map(|(_, data)| data)
+}
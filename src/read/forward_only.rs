@@ -0,0 +1,42 @@
+use super::AbsSeekPos;
+
+use std::io::{self, Read, Seek, SeekFrom};
+
+
+
+/// Adapt a [Read]able decompression stream into [Read] + [Seek] by only supporting forward seeks (by reading and
+/// discarding data.)  Seeking backwards results in an error.
+pub(crate) struct ForwardOnly<R> {
+    inner:  R,
+    length: u64,
+    pos:    u64,
+    what:   &'static str,
+}
+
+impl<R> ForwardOnly<R> {
+    /// `what` names the stream type for error messages ("zstd", "lzma", ...)
+    pub fn new(inner: R, length: u64, what: &'static str) -> Self {
+        Self { inner, length, pos: 0, what }
+    }
+}
+
+impl<R: Read> Read for ForwardOnly<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.pos += read as u64;
+        Ok(read)
+    }
+}
+
+impl<R: Read> Seek for ForwardOnly<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = AbsSeekPos(self.pos).offset_bounded(pos, self.length)?.0;
+        if target < self.pos {
+            return Err(io::Error::new(io::ErrorKind::Other, format!("Cannot seek backwards within a {} stream", self.what)));
+        }
+        let skip = target - self.pos;
+        let skipped = io::copy(&mut (&mut self.inner).take(skip), &mut io::sink())?;
+        self.pos += skipped;
+        Ok(self.pos)
+    }
+}
@@ -0,0 +1,304 @@
+use crate::{Error, Result};
+use super::{read_eocd_bytes, AesEntry, DirEntry, FileEntry, IntoCloneReadAtLen, ZipDuplicatePolicy, ZipReadOnly, ZipReadOnlyOptions, ZipWarning, ZipWarningKind};
+
+use read_write_at::ReadAt;
+
+use std::collections::*;
+use std::convert::*;
+use std::ffi::OsString;
+use std::io;
+use std::path::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, UNIX_EPOCH};
+
+
+
+const MAGIC     : &[u8; 8] = b"vfszipix";
+const VERSION   : u32 = 1;
+
+impl<IO: Clone + ReadAt> ZipReadOnly<IO> {
+    /// Create a new read-only zip filesystem, loading its index from the file at `cache` (as written by
+    /// [save_index_cache](Self::save_index_cache)) instead of parsing the central directory, if that cache is up to date.
+    ///
+    /// Caches are stale if the archive's length, end of central directory record, or archive comment have changed, or if
+    /// they were built with different indexing `options`.  Missing, stale, or corrupt caches are rebuilt from the archive
+    /// and rewritten, ignoring any errors while writing them.
+    ///
+    /// Nothing else is compared: an archive whose central directory was rewritten in place, without changing its length
+    /// or end of central directory record, will load the stale cache.  Delete the cache if you modify archives that way.
+    ///
+    /// [filename_decoder](ZipReadOnlyOptions::filename_decoder)s can't be compared, so use a different cache per decoder.
+    /// [lazy_index](ZipReadOnlyOptions::lazy_index) is unsupported.
+    ///
+    /// ```rust,no_run
+    /// # use vfs_zip::*;
+    /// # fn wrap() -> Result<()> {
+    /// let zip = std::fs::File::open("assets.zip").unwrap();
+    /// let zip = ZipReadOnly::new_with_index_cache(zip, ZipReadOnlyOptions::strict(), "assets.zip.index")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new_with_index_cache(cral: impl IntoCloneReadAtLen<ReadAt = IO>, options: ZipReadOnlyOptions, cache: impl AsRef<Path>) -> Result<Self> {
+        if options.lazy_index { return Err(Error::unsupported("vfs-zip doesn't support index caches with ZipReadOnlyOptions::lazy_index")) }
+        let cache = cache.as_ref();
+        let volumes = cral.volume_offsets();
+        let (cra, len) = cral.into_read_at_len().map_err(Error::io)?;
+        let eocd = read_eocd_bytes(&cra, len).map_err(Error::io)?;
+        if let Ok(bytes) = std::fs::read(cache) {
+            if let Ok(zro) = Self::from_index_cache(cra.clone(), len, &eocd, options.clone(), &bytes[..]) { return Ok(zro) }
+        }
+        let zro = Self::from_read_at_len(cra, len, &volumes[..], options)?;
+        let _ = write_replace(cache, &zro.index_cache(&eocd)[..]);
+        Ok(zro)
+    }
+
+    /// Load the index from `bytes`, failing if it's corrupt or doesn't match the archive / `options`
+    fn from_index_cache(cra: IO, len: u64, eocd: &[u8], options: ZipReadOnlyOptions, bytes: &[u8]) -> io::Result<Self> {
+        let mut r = CacheReader(bytes);
+        if r.take(MAGIC.len())? != MAGIC || r.u32()? != VERSION { return Err(invalid_data("not a vfs-zip index cache")) }
+        if r.u64()? != len || r.bytes()? != eocd || r.u32()? != options_key(&options) { return Err(invalid_data("stale index cache")) }
+
+        let comment = r.bytes()?.to_vec();
+        let mut warnings = Vec::new();
+        for _ in 0 .. r.u64()? {
+            let index   = r.u64()?.try_into().map_err(|_| invalid_data("warning index out of range"))?;
+            let name    = r.string()?;
+            let kind    = match r.u8()? {
+                0 => ZipWarningKind::Unreadable(r.string()?),
+                1 => ZipWarningKind::Backslash,
+                2 => ZipWarningKind::EmptyComponent,
+                3 => ZipWarningKind::AbsolutePath,
+                4 => ZipWarningKind::InvalidAesExtraField,
+                5 => ZipWarningKind::CaseCollision,
+                6 => ZipWarningKind::BackslashCollision,
+                7 => ZipWarningKind::NormalizationCollision,
+                8 => ZipWarningKind::Duplicate,
+                _ => return Err(invalid_data("invalid warning kind")),
+            };
+            warnings.push(ZipWarning { index, name, kind });
+        }
+
+        let mut files = BTreeMap::new();
+        for _ in 0 .. r.u64()? {
+            let path = r.string()?;
+            let header_offset   = r.u64()?;
+            let compressed      = r.u64()?;
+            let uncompressed    = r.u64()?;
+            #[allow(deprecated)] let compression = zip::CompressionMethod::from_u16(r.u16()?);
+            let crc32           = r.u32()?;
+            let aes = match r.u8()? {
+                0           => None,
+                strength    => Some(AesEntry { version: r.u16()?, strength }),
+            };
+            let modified = match r.u8()? {
+                0 => None,
+                1 => Some(UNIX_EPOCH.checked_add(r.duration()?).ok_or_else(|| invalid_data("invalid modified time"))?),
+                2 => Some(UNIX_EPOCH.checked_sub(r.duration()?).ok_or_else(|| invalid_data("invalid modified time"))?),
+                _ => return Err(invalid_data("invalid modified time")),
+            };
+            files.insert(path, FileEntry {
+                header_offset, compressed, uncompressed, compression, crc32, aes, modified,
+                version_made_by:        r.u16()?,
+                external_attributes:    r.u32()?,
+                comment:                r.bytes()?.to_vec(),
+                raw_name:               r.opt_string()?,
+                central_extra:          r.bytes()?.to_vec(),
+                local_extra:            r.bytes()?.to_vec(),
+            });
+        }
+
+        let mut dirs = BTreeMap::new();
+        for _ in 0 .. r.u64()? {
+            let path = r.string()?;
+            let children = (0 .. r.u64()?).map(|_| r.string()).collect::<io::Result<BTreeSet<_>>>()?;
+            dirs.insert(path, children);
+        }
+
+        let mut dir_entries = BTreeMap::new();
+        for _ in 0 .. r.u64()? {
+            let path = r.string()?;
+            dir_entries.insert(path, DirEntry { comment: r.bytes()?.to_vec(), raw_name: r.opt_string()? });
+        }
+
+        let mut folded = BTreeMap::new();
+        for _ in 0 .. r.u64()? {
+            let key = r.string()?;
+            folded.insert(key, r.string()?);
+        }
+
+        if !r.0.is_empty() { return Err(invalid_data("trailing data in index cache")) }
+        if !dirs.contains_key("") { return Err(invalid_data("index cache lacks a root directory")) }
+
+        Ok(Self {
+            io: cra,
+            len,
+            options,
+            files,
+            dirs,
+            comment,
+            dir_entries,
+            folded,
+            warnings,
+            lazy:            None,
+            #[cfg(any(feature = "zip-deflate", feature = "zip-deflate64"))]
+            inflate_indices: Default::default(),
+            #[cfg(feature = "zip-bzip2")]
+            bzip2_indices:   Default::default(),
+        })
+    }
+}
+
+impl<IO: ReadAt> ZipReadOnly<IO> {
+    /// Write this archive's index to the file at `path`, for [new_with_index_cache](Self::new_with_index_cache) to load
+    /// instead of parsing the central directory again.  Any existing file at `path` is replaced.
+    ///
+    /// Fails if the archive was opened with [lazy_index](ZipReadOnlyOptions::lazy_index).
+    pub fn save_index_cache(&self, path: impl AsRef<Path>) -> Result<()> {
+        if self.lazy.is_some() { return Err(Error::unsupported("vfs-zip doesn't support index caches with ZipReadOnlyOptions::lazy_index")) }
+        let eocd = read_eocd_bytes(&self.io, self.len).map_err(Error::io)?;
+        write_replace(path.as_ref(), &self.index_cache(&eocd)[..]).map_err(Error::io)
+    }
+
+    /// Serialize the index, keyed by the archive's length, `eocd` bytes, and indexing options
+    fn index_cache(&self, eocd: &[u8]) -> Vec<u8> {
+        let mut w = CacheWriter(Vec::new());
+        w.0.extend_from_slice(&MAGIC[..]);
+        w.u32(VERSION);
+        w.u64(self.len);
+        w.bytes(eocd);
+        w.u32(options_key(&self.options));
+
+        w.bytes(&self.comment[..]);
+        w.u64(self.warnings.len() as u64);
+        for warning in self.warnings.iter() {
+            w.u64(warning.index as u64);
+            w.bytes(warning.name.as_bytes());
+            match &warning.kind {
+                ZipWarningKind::Unreadable(err)         => { w.u8(0); w.bytes(err.as_bytes()); },
+                ZipWarningKind::Backslash               => w.u8(1),
+                ZipWarningKind::EmptyComponent          => w.u8(2),
+                ZipWarningKind::AbsolutePath            => w.u8(3),
+                ZipWarningKind::InvalidAesExtraField    => w.u8(4),
+                ZipWarningKind::CaseCollision           => w.u8(5),
+                ZipWarningKind::BackslashCollision      => w.u8(6),
+                ZipWarningKind::NormalizationCollision  => w.u8(7),
+                ZipWarningKind::Duplicate               => w.u8(8),
+            }
+        }
+
+        w.u64(self.files.len() as u64);
+        for (path, e) in self.files.iter() {
+            w.bytes(path.as_bytes());
+            w.u64(e.header_offset);
+            w.u64(e.compressed);
+            w.u64(e.uncompressed);
+            #[allow(deprecated)] w.u16(e.compression.to_u16());
+            w.u32(e.crc32);
+            match e.aes {
+                None        => w.u8(0),
+                Some(aes)   => { w.u8(aes.strength); w.u16(aes.version); },
+            }
+            match e.modified.map(|m| m.duration_since(UNIX_EPOCH)) {
+                None                => w.u8(0),
+                Some(Ok(after))     => { w.u8(1); w.duration(after); },
+                Some(Err(before))   => { w.u8(2); w.duration(before.duration()); },
+            }
+            w.u16(e.version_made_by);
+            w.u32(e.external_attributes);
+            w.bytes(&e.comment[..]);
+            w.opt_bytes(e.raw_name.as_ref().map(|n| n.as_bytes()));
+            w.bytes(&e.central_extra[..]);
+            w.bytes(&e.local_extra[..]);
+        }
+
+        w.u64(self.dirs.len() as u64);
+        for (path, children) in self.dirs.iter() {
+            w.bytes(path.as_bytes());
+            w.u64(children.len() as u64);
+            for child in children.iter() { w.bytes(child.as_bytes()); }
+        }
+
+        w.u64(self.dir_entries.len() as u64);
+        for (path, d) in self.dir_entries.iter() {
+            w.bytes(path.as_bytes());
+            w.bytes(&d.comment[..]);
+            w.opt_bytes(d.raw_name.as_ref().map(|n| n.as_bytes()));
+        }
+
+        w.u64(self.folded.len() as u64);
+        for (key, path) in self.folded.iter() {
+            w.bytes(key.as_bytes());
+            w.bytes(path.as_bytes());
+        }
+
+        w.0
+    }
+}
+
+/// The options that affect indexing, which a cache must have been built with to be reused
+fn options_key(options: &ZipReadOnlyOptions) -> u32 {
+    let duplicates = match options.duplicates {
+        ZipDuplicatePolicy::FirstWins   => 0,
+        ZipDuplicatePolicy::LastWins    => 1,
+        ZipDuplicatePolicy::Error       => 2,
+    };
+    (options.ignore_file_errors as u32)
+        | (options.keep_extra_fields as u32)            << 1
+        | (options.case_insensitive as u32)             << 2
+        | (options.backslash_separators as u32)         << 3
+        | (options.unicode_nfc as u32)                  << 4
+        | (options.filename_decoder.is_some() as u32)   << 5
+        | duplicates                                    << 6
+}
+
+/// Write `bytes` to a temporary file beside `path`, then rename it over `path`, so readers never see a partial cache.
+/// Temporary files are unique per process and call, so concurrent writers don't clobber each other's partial caches.
+fn write_replace(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    static NEXT_TMP : AtomicUsize = AtomicUsize::new(0);
+    let mut tmp = OsString::from(path.as_os_str());
+    tmp.push(format!(".{}-{}.tmp", std::process::id(), NEXT_TMP.fetch_add(1, Ordering::Relaxed)));
+    std::fs::write(&tmp, bytes)?;
+    let renamed = std::fs::rename(&tmp, path);
+    if renamed.is_err() { let _ = std::fs::remove_file(&tmp); }
+    renamed
+}
+
+/// Little endian, length prefixed encoding of index caches
+struct CacheWriter(Vec<u8>);
+
+impl CacheWriter {
+    fn u8 (&mut self, v: u8 ) { self.0.push(v); }
+    fn u16(&mut self, v: u16) { self.0.extend_from_slice(&v.to_le_bytes()); }
+    fn u32(&mut self, v: u32) { self.0.extend_from_slice(&v.to_le_bytes()); }
+    fn u64(&mut self, v: u64) { self.0.extend_from_slice(&v.to_le_bytes()); }
+    fn bytes(&mut self, b: &[u8]) { self.u64(b.len() as u64); self.0.extend_from_slice(b); }
+    fn opt_bytes(&mut self, b: Option<&[u8]>) { match b { None => self.u8(0), Some(b) => { self.u8(1); self.bytes(b); } } }
+    fn duration(&mut self, d: Duration) { self.u64(d.as_secs()); self.u32(d.subsec_nanos()); }
+}
+
+/// Reads what [CacheWriter] wrote, failing with [InvalidData](io::ErrorKind::InvalidData) if truncated
+struct CacheReader<'b>(&'b [u8]);
+
+impl<'b> CacheReader<'b> {
+    fn take(&mut self, n: usize) -> io::Result<&'b [u8]> {
+        if self.0.len() < n { return Err(invalid_data("truncated index cache")) }
+        let (taken, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn u8 (&mut self) -> io::Result<u8 > { Ok(self.take(1)?[0]) }
+    fn u16(&mut self) -> io::Result<u16> { let mut a = [0u8; 2]; a.copy_from_slice(self.take(2)?); Ok(u16::from_le_bytes(a)) }
+    fn u32(&mut self) -> io::Result<u32> { let mut a = [0u8; 4]; a.copy_from_slice(self.take(4)?); Ok(u32::from_le_bytes(a)) }
+    fn u64(&mut self) -> io::Result<u64> { let mut a = [0u8; 8]; a.copy_from_slice(self.take(8)?); Ok(u64::from_le_bytes(a)) }
+    fn bytes(&mut self) -> io::Result<&'b [u8]> { let n = self.u64()?; self.take(n.try_into().map_err(|_| invalid_data("truncated index cache"))?) }
+    fn string(&mut self) -> io::Result<String> { String::from_utf8(self.bytes()?.to_vec()).map_err(|_| invalid_data("invalid UTF-8 in index cache")) }
+    fn opt_string(&mut self) -> io::Result<Option<String>> { if self.u8()? == 0 { Ok(None) } else { self.string().map(Some) } }
+    fn duration(&mut self) -> io::Result<Duration> {
+        let (secs, nanos) = (self.u64()?, self.u32()?);
+        if nanos >= 1_000_000_000 { return Err(invalid_data("invalid duration in index cache")) }
+        Ok(Duration::new(secs, nanos))
+    }
+}
+
+fn invalid_data(message: &'static str) -> io::Error { io::Error::new(io::ErrorKind::InvalidData, message) }
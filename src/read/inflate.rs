@@ -0,0 +1,519 @@
+use super::AbsSeekPos;
+
+use std::io::{self, Read, Seek, SeekFrom};
+use std::sync::{Arc, Mutex};
+
+
+
+/// A seekable deflate (or Deflate64) decoder.
+///
+/// Seeking backwards (or far forwards) restarts decompression from the nearest [InflateCheckpoint] recorded in a
+/// (possibly shared) [InflateIndex], or from the start of the stream if there is no such checkpoint.
+/// Checkpoints are only recorded at block boundaries, where all we need to resume is a bit offset and the window.
+///
+/// Deflate64 ("enhanced deflate") only differs by having a 64 KB window, 16 extra bits for length code 285 instead of a
+/// fixed length of 258, and distance codes 30 and 31 - which are otherwise invalid.
+pub(crate) struct Inflate<R> {
+    input:      BitReader<R>,
+    index:      Arc<Mutex<InflateIndex>>,
+    deflate64:  bool,
+    window:     Box<[u8]>, // WINDOW_SIZE or DEFLATE64_WINDOW_SIZE
+    out_pos:    u64, // decompressed bytes produced so far
+    length:     u64, // expected decompressed length
+    target:     u64, // logical seek position
+    state:      State,
+    last_block: bool,
+    copy_len:   usize,
+    copy_dist:  usize,
+}
+
+enum State {
+    Header,
+    Stored(u16),
+    Huffman(Box<(Huffman, Huffman)>),
+    Done,
+}
+
+impl<R: Read + Seek> Inflate<R> {
+    #[cfg(feature = "zip-deflate")] pub fn new(input: R, length: u64, index: Arc<Mutex<InflateIndex>>) -> Self { Self::with_variant(input, length, index, false) }
+
+    #[cfg(feature = "zip-deflate64")] pub fn new_deflate64(input: R, length: u64, index: Arc<Mutex<InflateIndex>>) -> Self { Self::with_variant(input, length, index, true) }
+
+    fn with_variant(input: R, length: u64, index: Arc<Mutex<InflateIndex>>, deflate64: bool) -> Self {
+        Self {
+            input:      BitReader::new(input),
+            index,
+            deflate64,
+            window:     vec![0; if deflate64 { DEFLATE64_WINDOW_SIZE } else { WINDOW_SIZE }].into_boxed_slice(),
+            out_pos:    0,
+            length,
+            target:     0,
+            state:      State::Header,
+            last_block: false,
+            copy_len:   0,
+            copy_dist:  0,
+        }
+    }
+
+    fn restart(&mut self, checkpoint: Option<&InflateCheckpoint>) -> io::Result<()> {
+        match checkpoint {
+            Some(cp) => {
+                self.input.seek_to_bit(cp.bit_pos)?;
+                self.window.copy_from_slice(&cp.window[..]);
+                self.out_pos = cp.out_pos;
+            },
+            None => {
+                self.input.seek_to_bit(0)?;
+                self.out_pos = 0;
+            },
+        }
+        self.state      = State::Header;
+        self.last_block = false;
+        self.copy_len   = 0;
+        Ok(())
+    }
+
+    /// Decompress and discard data until `self.out_pos == self.target`, restarting from a checkpoint if that's faster.
+    fn reposition(&mut self) -> io::Result<()> {
+        {
+            let index = Arc::clone(&self.index);
+            let index = index.lock().unwrap();
+            let cp = index.checkpoint_at_or_before(self.target);
+            let cp_pos = cp.map_or(0, |cp| cp.out_pos);
+            if self.target < self.out_pos || cp_pos > self.out_pos {
+                self.restart(cp)?;
+            }
+        }
+
+        let mut scratch = [0u8; 8 * 1024];
+        while self.out_pos < self.target {
+            let n = ((self.target - self.out_pos).min(scratch.len() as u64)) as usize;
+            if self.inflate(&mut scratch[..n])? == 0 { break } // seeked past EOF
+        }
+        Ok(())
+    }
+
+    fn inflate(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mask = self.window.len() - 1;
+        let mut n = 0;
+        while n < buf.len() {
+            if self.copy_len > 0 {
+                let count = self.copy_len.min(buf.len() - n);
+                for _ in 0..count {
+                    let pos = self.out_pos as usize;
+                    let b = self.window[pos.wrapping_sub(self.copy_dist) & mask];
+                    self.window[pos & mask] = b;
+                    self.out_pos += 1;
+                    buf[n] = b;
+                    n += 1;
+                }
+                self.copy_len -= count;
+                continue;
+            }
+
+            match self.state {
+                State::Done => break,
+                State::Header => {
+                    if self.last_block {
+                        self.state = State::Done;
+                        continue;
+                    }
+                    self.index.lock().unwrap().record(self.input.bit_pos(), self.out_pos, &self.window);
+                    self.state = self.read_block_header()?;
+                },
+                State::Stored(ref mut remaining) => {
+                    if *remaining == 0 {
+                        self.state = State::Header;
+                        continue;
+                    }
+                    let count = (*remaining as usize).min(buf.len() - n);
+                    for _ in 0..count {
+                        let b = self.input.bits(8)? as u8;
+                        self.window[self.out_pos as usize & mask] = b;
+                        self.out_pos += 1;
+                        buf[n] = b;
+                        n += 1;
+                    }
+                    *remaining -= count as u16;
+                },
+                State::Huffman(ref tables) => {
+                    let (lit, dist) = &**tables;
+                    let sym = lit.decode(&mut self.input)? as usize;
+                    if sym < 256 {
+                        let b = sym as u8;
+                        self.window[self.out_pos as usize & mask] = b;
+                        self.out_pos += 1;
+                        buf[n] = b;
+                        n += 1;
+                    } else if sym == 256 {
+                        self.state = State::Header;
+                    } else {
+                        let sym = sym - 257;
+                        if sym >= LENGTH_BASE.len() { return Err(invalid_data("invalid deflate length code")) }
+                        let len = if self.deflate64 && sym == LENGTH_BASE.len() - 1 {
+                            3 + self.input.bits(16)? as usize
+                        } else {
+                            LENGTH_BASE[sym] as usize + self.input.bits(LENGTH_EXTRA[sym] as u32)? as usize
+                        };
+
+                        let dsym = dist.decode(&mut self.input)? as usize;
+                        let dist_codes = if self.deflate64 { DIST_BASE.len() } else { DIST_BASE.len() - 2 };
+                        if dsym >= dist_codes { return Err(invalid_data("invalid deflate distance code")) }
+                        let dist = DIST_BASE[dsym] as usize + self.input.bits(DIST_EXTRA[dsym] as u32)? as usize;
+                        if dist as u64 > self.out_pos || dist > self.window.len() { return Err(invalid_data("deflate distance too far back")) }
+
+                        self.copy_len  = len;
+                        self.copy_dist = dist;
+                    }
+                },
+            }
+        }
+        Ok(n)
+    }
+
+    fn read_block_header(&mut self) -> io::Result<State> {
+        self.last_block = self.input.bits(1)? == 1;
+        match self.input.bits(2)? {
+            0 => {
+                self.input.align_to_byte();
+                let len  = self.input.bits(16)? as u16;
+                let nlen = self.input.bits(16)? as u16;
+                if len != !nlen { return Err(invalid_data("deflate stored block length mismatch")) }
+                Ok(State::Stored(len))
+            },
+            1 => {
+                let mut lengths = [0u8; 288 + 32];
+                for (i, l) in lengths.iter_mut().enumerate() {
+                    *l = match i { 0..=143 => 8, 144..=255 => 9, 256..=279 => 7, 280..=287 => 8, _ => 5 };
+                }
+                let lit  = Huffman::new(&lengths[..288])?;
+                let dist = Huffman::new(&lengths[288..])?;
+                Ok(State::Huffman(Box::new((lit, dist))))
+            },
+            2 => {
+                let hlit  = self.input.bits(5)? as usize + 257;
+                let hdist = self.input.bits(5)? as usize + 1;
+                let hclen = self.input.bits(4)? as usize + 4;
+
+                const ORDER : [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+                let mut cl_lengths = [0u8; 19];
+                for &i in ORDER[..hclen].iter() {
+                    cl_lengths[i] = self.input.bits(3)? as u8;
+                }
+                let cl = Huffman::new(&cl_lengths[..])?;
+
+                let mut lengths = [0u8; 288 + 32];
+                let mut i = 0;
+                while i < hlit + hdist {
+                    let (value, repeat) = match cl.decode(&mut self.input)? {
+                        sym @ 0..=15    => (sym as u8, 1),
+                        16 if i == 0    => return Err(invalid_data("deflate code length repeat without a previous length")),
+                        16              => (lengths[i-1], 3 + self.input.bits(2)? as usize),
+                        17              => (0, 3 + self.input.bits(3)? as usize),
+                        _               => (0, 11 + self.input.bits(7)? as usize),
+                    };
+                    if i + repeat > hlit + hdist { return Err(invalid_data("deflate code lengths overflow")) }
+                    for l in lengths[i..i+repeat].iter_mut() { *l = value; }
+                    i += repeat;
+                }
+                if lengths[256] == 0 { return Err(invalid_data("deflate block has no end-of-block code")) }
+
+                let lit  = Huffman::new(&lengths[..hlit])?;
+                let dist = Huffman::new(&lengths[hlit..hlit+hdist])?;
+                Ok(State::Huffman(Box::new((lit, dist))))
+            },
+            _ => Err(invalid_data("invalid deflate block type")),
+        }
+    }
+}
+
+impl<R: Read + Seek> Read for Inflate<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.target != self.out_pos { self.reposition()?; }
+        let n = self.inflate(buf)?;
+        self.target = self.out_pos;
+        Ok(n)
+    }
+}
+
+impl<R: Read + Seek> Seek for Inflate<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.target = AbsSeekPos(self.target).offset_bounded(pos, self.length)?.0;
+        Ok(self.target)
+    }
+}
+
+
+
+/// A seekable deflate decoder that streams through [flate2] until seeked backwards (or forwards past a checkpoint already
+/// recorded by another reader), only then switching to [Inflate] to record and restart from checkpoints.
+#[cfg(feature = "zip-deflate")]
+pub(crate) struct SeekableDeflate<R> {
+    decoder:    Decoder<R>,
+    index:      Arc<Mutex<InflateIndex>>,
+    length:     u64, // expected decompressed length
+    target:     u64, // logical seek position
+}
+
+#[cfg(feature = "zip-deflate")]
+enum Decoder<R> {
+    Flate(flate2::read::DeflateDecoder<R>, u64), // decompressed bytes produced so far
+    Inflate(Inflate<R>),
+    Switching, // only while switching from Flate to Inflate
+}
+
+#[cfg(feature = "zip-deflate")]
+impl<R: Read + Seek> SeekableDeflate<R> {
+    pub fn new(input: R, length: u64, index: Arc<Mutex<InflateIndex>>) -> Self {
+        Self { decoder: Decoder::Flate(flate2::read::DeflateDecoder::new(input), 0), index, length, target: 0 }
+    }
+
+    /// Switch to [Inflate] (restarting from the start of the stream, or a checkpoint) if `target` is behind the
+    /// sequential decoder, or ahead of a recorded checkpoint that it hasn't reached yet.
+    fn switch_if_needed(&mut self) -> io::Result<()> {
+        let pos = match self.decoder { Decoder::Flate(_, pos) => pos, _ => return Ok(()) };
+        let cp_pos = self.index.lock().unwrap().checkpoint_at_or_before(self.target).map_or(0, |cp| cp.out_pos);
+        if self.target >= pos && cp_pos <= pos { return Ok(()) }
+
+        let mut input = match std::mem::replace(&mut self.decoder, Decoder::Switching) {
+            Decoder::Flate(flate, _)    => flate.into_inner(),
+            _                           => unreachable!(),
+        };
+        input.seek(SeekFrom::Start(0))?;
+        let mut inflate = Inflate::new(input, self.length, Arc::clone(&self.index));
+        inflate.seek(SeekFrom::Start(self.target))?;
+        self.decoder = Decoder::Inflate(inflate);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "zip-deflate")]
+impl<R: Read + Seek> Read for SeekableDeflate<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.switch_if_needed()?;
+        let n = match self.decoder {
+            Decoder::Flate(ref mut flate, ref mut pos) => {
+                let mut scratch = [0u8; 8 * 1024];
+                while *pos < self.target {
+                    let n = ((self.target - *pos).min(scratch.len() as u64)) as usize;
+                    let n = flate.read(&mut scratch[..n])?;
+                    if n == 0 { return Ok(0) } // seeked past EOF
+                    *pos += n as u64;
+                }
+                let n = flate.read(buf)?;
+                *pos += n as u64;
+                n
+            },
+            Decoder::Inflate(ref mut inflate) => inflate.read(buf)?,
+            Decoder::Switching => return Err(io::Error::new(io::ErrorKind::Other, "deflate decoder failed while switching to a seekable decoder")),
+        };
+        self.target += n as u64;
+        Ok(n)
+    }
+}
+
+#[cfg(feature = "zip-deflate")]
+impl<R: Read + Seek> Seek for SeekableDeflate<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.target = AbsSeekPos(self.target).offset_bounded(pos, self.length)?.0;
+        if let Decoder::Inflate(ref mut inflate) = self.decoder { inflate.seek(SeekFrom::Start(self.target))?; }
+        Ok(self.target)
+    }
+}
+
+
+/// Checkpoints shared between every [Inflate] reading the same deflate stream, dropped once they've all been closed
+pub(crate) struct InflateIndex {
+    interval:       u64,
+    checkpoints:    Vec<InflateCheckpoint>, // sorted by out_pos
+}
+
+/// Everything needed to resume decompression at the start of a deflate block
+struct InflateCheckpoint {
+    bit_pos:    u64,
+    out_pos:    u64,
+    window:     Box<[u8]>,
+}
+
+impl InflateIndex {
+    /// Record a checkpoint roughly every `interval` decompressed bytes (0 to disable checkpoints)
+    pub fn new(interval: u64) -> Self { Self { interval, checkpoints: Vec::new() } }
+
+    fn checkpoint_at_or_before(&self, out_pos: u64) -> Option<&InflateCheckpoint> {
+        match self.checkpoints.binary_search_by_key(&out_pos, |cp| cp.out_pos) {
+            Ok(i)   => Some(&self.checkpoints[i]),
+            Err(0)  => None,
+            Err(i)  => Some(&self.checkpoints[i-1]),
+        }
+    }
+
+    fn record(&mut self, bit_pos: u64, out_pos: u64, window: &[u8]) {
+        if self.interval == 0 { return }
+        let prev = self.checkpoint_at_or_before(out_pos).map_or(0, |cp| cp.out_pos);
+        if out_pos < prev + self.interval { return }
+        let i = self.checkpoints.binary_search_by_key(&out_pos, |cp| cp.out_pos).unwrap_err();
+        self.checkpoints.insert(i, InflateCheckpoint { bit_pos, out_pos, window: window.into() });
+    }
+}
+
+
+
+const WINDOW_SIZE           : usize = 32 * 1024;
+const DEFLATE64_WINDOW_SIZE : usize = 64 * 1024;
+
+const LENGTH_BASE   : [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA  : [u8;  29] = [0, 0, 0, 0, 0, 0, 0,  0,  1,  1,  1,  1,  2,  2,  2,  2,  3,  3,  3,  3,  4,  4,  4,   4,   5,   5,   5,   5,   0];
+const DIST_BASE     : [u16; 32] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577, 32769, 49153]; // last 2 are Deflate64 only
+const DIST_EXTRA    : [u8;  32] = [0, 0, 0, 0, 1, 1, 2,  2,  3,  3,  4,  4,  5,  5,   6,   6,   7,   7,   8,   8,    9,    9,   10,   10,   11,   11,   12,    12,    13,    13,    14,    14];
+
+fn invalid_data(message: &'static str) -> io::Error { io::Error::new(io::ErrorKind::InvalidData, message) }
+
+
+
+/// LSB-first bit reader that tracks its absolute bit position within the stream
+struct BitReader<R> {
+    inner:      R,
+    buf:        Box<[u8]>,
+    start:      usize,  // buf[start..end] has yet to be shifted into bits
+    end:        usize,
+    end_pos:    u64,    // stream byte offset of buf[end]
+    bits:       u64,
+    nbits:      u32,
+}
+
+impl<R: Read + Seek> BitReader<R> {
+    fn new(inner: R) -> Self {
+        Self { inner, buf: vec![0; 32 * 1024].into_boxed_slice(), start: 0, end: 0, end_pos: 0, bits: 0, nbits: 0 }
+    }
+
+    fn bit_pos(&self) -> u64 { (self.end_pos - (self.end - self.start) as u64) * 8 - self.nbits as u64 }
+
+    fn seek_to_bit(&mut self, bit_pos: u64) -> io::Result<()> {
+        let byte = bit_pos / 8;
+        self.inner.seek(SeekFrom::Start(byte))?;
+        self.start      = 0;
+        self.end        = 0;
+        self.end_pos    = byte;
+        self.bits       = 0;
+        self.nbits      = 0;
+        self.bits((bit_pos % 8) as u32)?;
+        Ok(())
+    }
+
+    fn refill(&mut self) -> io::Result<()> {
+        while self.nbits <= 56 {
+            if self.start == self.end {
+                let read = self.inner.read(&mut self.buf[..])?;
+                if read == 0 { break }
+                self.start      = 0;
+                self.end        = read;
+                self.end_pos   += read as u64;
+            }
+            self.bits |= (self.buf[self.start] as u64) << self.nbits;
+            self.start += 1;
+            self.nbits += 8;
+        }
+        Ok(())
+    }
+
+    fn consume(&mut self, n: u32) -> io::Result<()> {
+        if n > self.nbits { return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "deflate stream ended unexpectedly")) }
+        self.bits >>= n;
+        self.nbits -= n;
+        Ok(())
+    }
+
+    fn bits(&mut self, n: u32) -> io::Result<u32> {
+        if n == 0 { return Ok(0) }
+        if self.nbits < n { self.refill()?; }
+        let value = (self.bits & ((1 << n) - 1)) as u32;
+        self.consume(n)?;
+        Ok(value)
+    }
+
+    fn align_to_byte(&mut self) {
+        let n = self.nbits % 8;
+        self.bits >>= n;
+        self.nbits -= n;
+    }
+}
+
+
+
+const FAST_BITS : u32 = 10;
+
+/// Canonical huffman decoding table
+struct Huffman {
+    counts:     [u16; 16],      // # of codes of each length
+    symbols:    Vec<u16>,       // symbols ordered by code
+    fast:       Box<[u16]>,     // bit-reversed code prefix -> (symbol << 4 | length), or 0 if the code is longer than FAST_BITS
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> io::Result<Self> {
+        let mut counts = [0u16; 16];
+        for &l in lengths { counts[l as usize] += 1; }
+        counts[0] = 0;
+
+        let mut left = 1i32;
+        for &count in counts[1..].iter() {
+            left = (left << 1) - count as i32;
+            if left < 0 { return Err(invalid_data("over-subscribed deflate huffman code")) }
+        }
+
+        let mut offsets = [0u16; 16];
+        for len in 1..15 { offsets[len+1] = offsets[len] + counts[len]; }
+        let mut symbols = vec![0; lengths.len()];
+        for (sym, &l) in lengths.iter().enumerate().filter(|(_, &l)| l != 0) {
+            symbols[offsets[l as usize] as usize] = sym as u16;
+            offsets[l as usize] += 1;
+        }
+
+        let mut next_code = [0u32; 16];
+        let mut code = 0;
+        for len in 1..16 {
+            code = (code + counts[len-1] as u32) << 1;
+            next_code[len] = code;
+        }
+        let mut fast = vec![0u16; 1 << FAST_BITS].into_boxed_slice();
+        for (sym, &l) in lengths.iter().enumerate() {
+            let l = l as u32;
+            if l == 0 { continue }
+            let code = next_code[l as usize];
+            next_code[l as usize] += 1;
+            if l > FAST_BITS { continue }
+            let mut i = (0..l).fold(0, |rev, bit| rev << 1 | (code >> bit) & 1) as usize; // bit-reverse code
+            while i < fast.len() {
+                fast[i] = (sym as u16) << 4 | l as u16;
+                i += 1 << l;
+            }
+        }
+
+        Ok(Self { counts, symbols, fast })
+    }
+
+    fn decode<R: Read + Seek>(&self, input: &mut BitReader<R>) -> io::Result<u16> {
+        if input.nbits < 15 { input.refill()?; }
+        let peek = input.bits;
+
+        let e = self.fast[(peek & ((1 << FAST_BITS) - 1)) as usize];
+        if e != 0 {
+            input.consume((e & 15) as u32)?;
+            return Ok(e >> 4);
+        }
+
+        let (mut code, mut first, mut index) = (0u32, 0u32, 0u32);
+        for len in 1..16 {
+            code |= ((peek >> (len - 1)) & 1) as u32;
+            let count = self.counts[len] as u32;
+            if code < first + count {
+                input.consume(len as u32)?;
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(invalid_data("invalid deflate huffman code"))
+    }
+}
@@ -9,7 +9,8 @@ use std::sync::{Arc, Mutex};
 
 
 /// Convert into a (cheaply!) [Clone]able [ReadAt] + [u64] length.  Implementations include:<br>
-/// [File], [Vec]\<u8\>, [Arc]\<\[u8\]\>, [Box]\<\[u8\]\>, \&\[u8\], [Mutex]\<[Read] + [Seek]\>, and [Arc]\<[Mutex]\<[Read] + [Seek]\>\>.
+/// [File], [Vec]\<u8\>, [Arc]\<\[u8\]\>, [Box]\<\[u8\]\>, \&\[u8\], [Mutex]\<[Read] + [Seek]\>, [Arc]\<[Mutex]\<[Read] + [Seek]\>\>,
+/// and [MultiVolume](crate::MultiVolume).
 ///
 /// [ReadAt]:   https://docs.rs/read_write_at/0.1.0/read_write_at/trait.ReadAt.html
 pub trait IntoCloneReadAtLen {
@@ -22,6 +23,12 @@ pub trait IntoCloneReadAtLen {
     ///
     /// [ReadAt]:   https://docs.rs/read_write_at/0.1.0/read_write_at/trait.ReadAt.html
     fn into_read_at_len(self) -> io::Result<(Self::ReadAt, u64)>;
+
+    /// The offset of each volume within the [ReadAt], for spanned archives whose central directory locates entries by
+    /// disk number and offset within that disk (see [MultiVolume](crate::MultiVolume).)  Empty for single volume archives.
+    ///
+    /// [ReadAt]:   https://docs.rs/read_write_at/0.1.0/read_write_at/trait.ReadAt.html
+    fn volume_offsets(&self) -> Vec<u64> { Vec::new() }
 }
 
 #[cfg(any(target_os = "redox", unix, target_os = "vxworks", target_os = "hermit", windows))]
@@ -0,0 +1,157 @@
+use crate::{Error, Result};
+use super::{decode_name, invalid_name, CentralDirectory, CentralRecord, FileEntry, FilenameDecoder, ZipDuplicatePolicy, ZipReadOnlyOptions, ZipWarning, ZipWarningKind};
+
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+use std::sync::{Mutex, MutexGuard};
+
+
+
+/// A compact, sorted table of central directory record offsets.  Entry names are decoded (if they even need to be) on
+/// demand, and only validated once a lookup or listing reaches them.  See [ZipReadOnlyOptions::lazy_index].
+pub(crate) struct LazyIndex {
+    cd:                 CentralDirectory,
+    offsets:            Vec<u32>, // of central directory records within `cd.bytes`, sorted by (path, is_dir), without duplicates
+    decoder:            Option<FilenameDecoder>,
+    keep_extra_fields:  bool,
+    last_file:          Mutex<Option<(String, FileEntry)>>, // the most recent `file` lookup: opening a file looks it up repeatedly
+}
+
+impl LazyIndex {
+    pub fn new(cd: CentralDirectory, options: &ZipReadOnlyOptions, warnings: &mut Vec<ZipWarning>) -> Result<Self> {
+        if options.case_insensitive || options.backslash_separators || options.unicode_nfc {
+            return Err(Error::unsupported("vfs-zip doesn't support ZipReadOnlyOptions::lazy_index with case_insensitive, backslash_separators, or unicode_nfc"));
+        }
+        if cd.bytes.len() > std::u32::MAX as usize {
+            return Err(Error::unsupported("vfs-zip doesn't support ZipReadOnlyOptions::lazy_index for central directories larger than 4 GiB"));
+        }
+
+        let mut indexed = Vec::new(); // (central directory index, offset)
+        for (i, record) in cd.records().enumerate() {
+            let (offset, _) = record.map_err(Error::io)?;
+            indexed.push((i, offset as u32));
+        }
+
+        let decoder = options.filename_decoder.as_ref();
+        let mut keyed : Vec<_> = indexed.into_iter().map(|(i, offset)| (entry_path(&cd, decoder, offset), i, offset)).collect(); // decode each path once
+        keyed.sort_unstable(); // duplicates stay in central directory order
+        let mut offsets : Vec<u32> = Vec::with_capacity(keyed.len());
+        let mut last_key = None;
+        for (key, i, offset) in keyed {
+            let last = match offsets.last_mut() {
+                Some(last) if last_key.as_ref() == Some(&key) => last,
+                _ => { offsets.push(offset); last_key = Some(key); continue },
+            };
+            if key.1 { *last = offset; continue } // like `dirs`, later explicit directory entries overwrite earlier ones
+            if !is_valid(&cd, decoder, *last) { *last = offset; continue } // invalid entries mustn't shadow valid duplicates
+            if !is_valid(&cd, decoder, offset) { continue }
+            match options.duplicates {
+                ZipDuplicatePolicy::Error if !options.ignore_file_errors => return Err(Error::unsupported("vfs-zip found multiple entries with the same path (see ZipReadOnlyOptions::duplicates)")),
+                ZipDuplicatePolicy::Error | ZipDuplicatePolicy::FirstWins => {},
+                ZipDuplicatePolicy::LastWins => *last = offset,
+            }
+            warnings.push(ZipWarning { index: i, name: key.0.into_owned(), kind: ZipWarningKind::Duplicate });
+        }
+        offsets.shrink_to_fit();
+        warnings.sort_by_key(|w| w.index);
+
+        let decoder = options.filename_decoder.clone();
+        Ok(Self { cd, offsets, decoder, keep_extra_fields: options.keep_extra_fields, last_file: Mutex::new(None) })
+    }
+
+    /// The file entry at `path`
+    pub(super) fn file(&self, path: &str) -> Option<FileEntry> {
+        if let Some((last_path, entry)) = self.last_file().as_ref() {
+            if last_path == path { return Some(entry.clone()) }
+        }
+
+        let offset = *self.offsets.get(self.lower_bound(path))?;
+        if self.path(offset) != (Cow::Borrowed(path), false) { return None }
+        let record = self.valid_record(offset)?;
+        let entry = FileEntry::from_record(&record, self.keep_extra_fields)?;
+        *self.last_file() = Some((path.to_owned(), entry.clone()));
+        Some(entry)
+    }
+
+    /// The central directory record of the explicit directory entry at `path`, if there is one
+    pub fn dir_record(&self, path: &str) -> Option<CentralRecord<'_>> {
+        let i = self.lower_bound(path);
+        let offset = self.offsets[i..].iter().take(2).cloned().find(|&o| self.path(o) == (Cow::Borrowed(path), true))?;
+        self.valid_record(offset)
+    }
+
+    /// Is there a directory at `path`, either explicitly or implied by the paths of other entries?
+    pub fn is_dir(&self, path: &str) -> bool {
+        if path.is_empty() || self.dir_record(path).is_some() { return true }
+        let prefix = format!("{}/", path);
+        self.offsets[self.lower_bound(&prefix)..].iter()
+            .take_while(|&&o| self.path(o).0.starts_with(&prefix[..]))
+            .any(|&o| self.is_valid(o))
+    }
+
+    /// The names of the files and directories directly within the directory at `path`
+    pub fn dir_children(&self, path: &str) -> Option<BTreeSet<String>> {
+        if !self.is_dir(path) { return None }
+        let prefix = if path.is_empty() { String::new() } else { format!("{}/", path) };
+        let mut children = BTreeSet::new();
+        let mut i = self.lower_bound(&prefix);
+        while let Some(&offset) = self.offsets.get(i) {
+            let path = self.path(offset).0;
+            if !path.starts_with(&prefix[..]) { break }
+            if !self.is_valid(offset) { i += 1; continue }
+            let rest = &path[prefix.len()..];
+            match rest.find('/') {
+                None => {
+                    if !rest.is_empty() { children.insert(rest.to_owned()); }
+                    i += 1;
+                },
+                Some(slash) => { // skip everything else within this subdirectory ('0' follows '/')
+                    let child = &rest[..slash];
+                    i = self.lower_bound(&format!("{}{}0", prefix, child));
+                    children.insert(child.to_owned());
+                },
+            }
+        }
+        Some(children)
+    }
+
+    /// The index of the first entry whose path isn't less than `path`
+    fn lower_bound(&self, path: &str) -> usize {
+        match self.offsets.binary_search_by(|&o| self.path(o).0.as_bytes().cmp(path.as_bytes()).then(Ordering::Greater)) { Ok(i) | Err(i) => i }
+    }
+
+    /// The `file` lookup cache, locked only to check or replace it, so concurrent lookups of different paths don't serialize
+    fn last_file(&self) -> MutexGuard<'_, Option<(String, FileEntry)>> { self.last_file.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) }
+
+    fn path(&self, offset: u32) -> (Cow<'_, str>, bool) { entry_path(&self.cd, self.decoder.as_ref(), offset) }
+
+    fn is_valid(&self, offset: u32) -> bool { is_valid(&self.cd, self.decoder.as_ref(), offset) }
+
+    /// The record at `offset`, or [None] if it's invalid (and would've been skipped by the eager index)
+    fn valid_record(&self, offset: u32) -> Option<CentralRecord<'_>> {
+        if !self.is_valid(offset) { return None }
+        self.cd.record_at(offset as usize).ok().map(|(record, _)| record)
+    }
+}
+
+/// (decoded path without any trailing `/`, is a directory entry) of the record at `offset`
+fn entry_path<'a>(cd: &'a CentralDirectory, decoder: Option<&FilenameDecoder>, offset: u32) -> (Cow<'a, str>, bool) {
+    let name = match cd.record_at(offset as usize) {
+        Ok((record, _)) => decode_name(&record, decoder),
+        Err(_)          => Cow::Borrowed(""), // unreachable: every record was parsed successfully while indexing
+    };
+    let is_dir = name.ends_with('/');
+    let path = match name {
+        Cow::Borrowed(name) => Cow::Borrowed(name.trim_end_matches('/')),
+        Cow::Owned(name)    => Cow::Owned(name.trim_end_matches('/').to_owned()),
+    };
+    (path, is_dir)
+}
+
+/// Does the record at `offset` have a name vfs-zip can represent, and (for AES encrypted files) a valid 0x9901 extra field?
+fn is_valid(cd: &CentralDirectory, decoder: Option<&FilenameDecoder>, offset: u32) -> bool {
+    let record = match cd.record_at(offset as usize) { Ok((record, _)) => record, Err(_) => return false };
+    let name = decode_name(&record, decoder);
+    invalid_name(&name).is_none() && (name.ends_with('/') || FileEntry::from_record(&record, false).is_some())
+}
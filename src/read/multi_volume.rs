@@ -0,0 +1,90 @@
+use super::IntoCloneReadAtLen;
+
+use read_write_at::ReadAt;
+
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io;
+use std::path::*;
+use std::sync::Arc;
+
+
+
+/// The volumes of a multi-volume zip archive (e.g. `scans.z01`, `scans.z02`, ..., `scans.zip`), concatenated into a
+/// single [ReadAt] that [ZipReadOnly](crate::ZipReadOnly) can open like any other archive.  Handles both:
+///
+/// *   "Split" archives, which are just concatenated byte ranges: every entry is on disk 0, with offsets from the start
+///     of the first volume.
+/// *   "Spanned" archives (as written by WinZip or `zip -s`), where entries are located by disk number and offset
+///     within that disk's volume.
+///
+/// ```rust,no_run
+/// # use vfs_zip::*;
+/// # fn wrap() -> Result<()> {
+/// let volumes = MultiVolume::open("scans.zip").unwrap(); // also opens scans.z01, scans.z02, ...
+/// let zip = ZipReadOnly::new_strict(volumes)?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [ReadAt]:   https://docs.rs/read_write_at/0.1.0/read_write_at/trait.ReadAt.html
+pub struct MultiVolume<IO> {
+    volumes:    Arc<[(u64, IO)]>, // (offset within the concatenation, volume), in disk order
+    len:        u64,
+}
+
+impl<IO> Clone for MultiVolume<IO> { fn clone(&self) -> Self { Self { volumes: Arc::clone(&self.volumes), len: self.len } } }
+
+impl<IO: Clone + ReadAt> MultiVolume<IO> {
+    /// Concatenate `volumes` in disk order, ending with the volume containing the end of central directory record
+    /// (usually `.zip`.)
+    pub fn new<V: IntoCloneReadAtLen<ReadAt = IO>>(volumes: impl IntoIterator<Item = V>) -> io::Result<Self> {
+        let mut concatenated = Vec::new();
+        let mut len = 0u64;
+        for volume in volumes {
+            let (io, volume_len) = volume.into_read_at_len()?;
+            concatenated.push((len, io));
+            len = len.checked_add(volume_len).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "volumes too large"))?;
+        }
+        if concatenated.is_empty() { return Err(io::Error::new(io::ErrorKind::InvalidInput, "no volumes")) }
+        Ok(Self { volumes: Arc::from(concatenated), len })
+    }
+
+    /// The number of volumes
+    pub fn volumes(&self) -> usize { self.volumes.len() }
+}
+
+impl MultiVolume<<File as IntoCloneReadAtLen>::ReadAt> {
+    /// Open the volumes of a multi-volume archive from the path of its last volume (e.g. `scans.zip`), which is preceded
+    /// by every volume with the same name and a numbered extension (`scans.z01`, `scans.z02`, ...) that exists.
+    pub fn open(last: impl AsRef<Path>) -> io::Result<Self> {
+        let last = last.as_ref();
+        let z = if last.extension().map_or(false, |ext| ext == "ZIP") { "Z" } else { "z" };
+        let mut paths = Vec::new();
+        loop {
+            let path = last.with_extension(format!("{}{:02}", z, paths.len() + 1));
+            if !path.is_file() { break }
+            paths.push(path);
+        }
+        paths.push(last.to_path_buf());
+        Self::new(paths.iter().map(File::open).collect::<io::Result<Vec<_>>>()?)
+    }
+}
+
+impl<IO: ReadAt> ReadAt for MultiVolume<IO> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        if offset >= self.len || buf.is_empty() { return Ok(0) }
+        // the last volume starting at or before `offset`, skipping empty volumes
+        let i = match self.volumes.binary_search_by(|&(start, _)| if start <= offset { Ordering::Less } else { Ordering::Greater }) { Ok(i) | Err(i) => i - 1 };
+        let (start, io) = &self.volumes[i];
+        let end = self.volumes.get(i+1).map_or(self.len, |&(next, _)| next);
+        let n = (buf.len() as u64).min(end - offset) as usize; // don't read past the end of this volume
+        io.read_at(&mut buf[..n], offset - start)
+    }
+}
+
+impl<IO: Clone + ReadAt> IntoCloneReadAtLen for MultiVolume<IO> {
+    type ReadAt = Self;
+    fn into_read_at_len(self) -> io::Result<(Self::ReadAt, u64)> { let len = self.len; Ok((self, len)) }
+    fn volume_offsets(&self) -> Vec<u64> { self.volumes.iter().map(|&(start, _)| start).collect() }
+}
@@ -0,0 +1,14 @@
+use std::borrow::Cow;
+
+
+
+/// Unicode NFC normalize `s`, borrowing it if it's already normalized.  Only used with
+/// [ZipReadOnlyOptions::unicode_nfc](crate::ZipReadOnlyOptions::unicode_nfc).
+#[cfg(feature = "unicode-nfc")]
+pub(crate) fn to_nfc(s: &str) -> Cow<'_, str> {
+    use unicode_normalization::{is_nfc, UnicodeNormalization};
+    if is_nfc(s) { Cow::Borrowed(s) } else { Cow::Owned(s.nfc().collect()) }
+}
+
+#[cfg(not(feature = "unicode-nfc"))]
+pub(crate) fn to_nfc(s: &str) -> Cow<'_, str> { Cow::Borrowed(s) }
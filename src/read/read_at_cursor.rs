@@ -32,7 +32,7 @@ impl<RA: ReadAt> Read for ReadAtCursor<RA> {
     }
 
     fn read_exact(&mut self, buf: &mut [u8]) -> io::Result<()> {
-        let dst = self.offset.checked_add(buf.len() as u64).ok_or_else(|| io::ErrorKind::UnexpectedEof)?;
+        let dst = self.offset.checked_add(buf.len() as u64).ok_or(io::ErrorKind::UnexpectedEof)?;
         self.ra.read_exact_at(buf, self.offset)?;
         self.offset = dst;
         Ok(())
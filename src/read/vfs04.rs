@@ -1,50 +1,235 @@
-use crate::{error, ReadRange, ReadAtCursor, ZipReadOnly};
+#[cfg(feature = "zip-aes")] use crate::AesDecrypt;
+#[cfg(feature = "zip-deflate64")] use crate::Inflate;
+#[cfg(feature = "zip-bzip2")] use crate::Bzip2Blocks;
+#[cfg(feature = "zip-deflate")] use crate::SeekableDeflate;
+use crate::{error, Crc32Check, ForwardOnly, ReadRange, ReadAtCursor, ZipCrypto, ZipReadOnly};
+use super::{normalize_file, normalize_path_dir, read_local_header, FileEntry, LocalHeader};
 use vfs04::*;
 use read_write_at::ReadAt;
+use std::borrow::Cow;
 use std::convert::*;
-use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::io::{self, Read, Write};
 
-const KB : u64 = 1024;
-const MB : u64 = 1024 * KB;
-const GB : u64 = 1024 * MB;
+impl<IO: ReadAt> ZipReadOnly<IO> {
+    fn normalize_file<'s>(&self, orig: &'s str) -> VfsResult<&'s str> {
+        normalize_file(orig).ok_or_else(|| VfsError::InvalidPath { path: orig.into() })
+    }
 
-// TODO: Make all this configurable per-fs
+    fn normalize_path_dir<'s>(&self, orig: &'s str) -> VfsResult<(&'s str, bool)> {
+        normalize_path_dir(orig).ok_or_else(|| VfsError::InvalidPath { path: orig.into() })
+    }
+}
 
-/// Above this file size, attempt to decompress files straight from disk instead of copying them into memory first.
-const LIMIT_PREFER_IN_MEMORY : u64 = 1*KB;
+impl<IO: Clone + ReadAt + Send + Sync + 'static> ZipReadOnly<IO> {
+    /// Read the symlink at `path`, returning its target.  Targets are relative to the symlink's parent directory.
+    ///
+    /// Symlinks are entries created on Unix hosts (e.g. by `zip -y`) with a file mode of `S_IFLNK`, whose contents are
+    /// the target path.  See also [ZipMetadata::is_symlink](crate::ZipMetadata::is_symlink).
+    pub fn read_link(&self, orig: &str) -> VfsResult<String> {
+        let path = self.canonical(self.normalize_file(orig)?);
+        let path = &*path;
+        match self.file(path) {
+            Some(e) if e.is_symlink() => self.read_symlink(path, &e),
+            Some(_) => Err(VfsError::Other { message: format!("\"{}\" is not a symlink", orig) }),
+            None    => Err(VfsError::FileNotFound { path: orig.into() }),
+        }
+    }
 
-/// Above this file size, fail to read the file if it would require reading into memory first.
-const LIMIT_ALLOW_IN_MEMORY : u64 = 1*GB;
+    /// Map the normalized `path` to the spelling used by `files` / `dirs` (see [canonical](Self::canonical)), and if
+    /// [ZipReadOnlyOptions::follow_symlinks](crate::ZipReadOnlyOptions::follow_symlinks) is enabled, resolve any
+    /// symlinks within it.  Links that escape the archive root, or that take more than `MAX_SYMLINK_HOPS` to resolve
+    /// (probably a loop), result in errors.
+    fn resolve<'s>(&self, path: &'s str) -> VfsResult<Cow<'s, str>> {
+        const MAX_SYMLINK_HOPS : usize = 40; // same as Linux's ELOOP limit
+        if !self.options.follow_symlinks { return Ok(self.canonical(path)) }
 
-impl<IO: ReadAt> ZipReadOnly<IO> {
-    fn normalize_file<'s>(&self, orig: &'s str) -> VfsResult<&'s str> {
-        if orig.contains('\\') || orig.ends_with('/') {
-            return Err(VfsError::InvalidPath { path: orig.into() }); // Invalid path for file
+        let mut pending : Vec<String> = path.split('/').rev().map(String::from).collect(); // components left to resolve (reversed)
+        let mut resolved = String::new();
+        let mut hops = 0;
+        while let Some(component) = pending.pop() {
+            match component.as_str() {
+                "" | "." => continue,
+                ".." => {
+                    if resolved.is_empty() { return Err(escapes_root(path)) }
+                    resolved.truncate(resolved.rfind('/').unwrap_or(0));
+                    continue;
+                },
+                _ => {},
+            }
+
+            let parent_len = resolved.len();
+            if !resolved.is_empty() { resolved.push('/'); }
+            resolved.push_str(&component);
+            if self.options.case_insensitive || self.options.unicode_nfc { resolved = self.canonical(&resolved).into_owned(); }
+
+            if let Some(e) = self.file(&resolved).filter(|e| e.is_symlink()) {
+                hops += 1;
+                if hops > MAX_SYMLINK_HOPS {
+                    return Err(VfsError::IoError(io::Error::new(io::ErrorKind::Other, format!("too many levels of symlinks resolving \"{}\"", path))));
+                }
+                let target = self.read_symlink(&resolved, &e)?;
+                if target.starts_with('/') { return Err(escapes_root(path)) } // absolute links point outside the archive
+                pending.extend(target.split('/').rev().map(String::from));
+                resolved.truncate(parent_len);
+            }
         }
-        let path = if orig.starts_with('/') { &orig[1..] } else { orig };
-        if path.split('/').any(|c| c == "" || c == "." || c == "..") {
-            return Err(VfsError::InvalidPath { path: orig.into() });
+        Ok(Cow::Owned(resolved))
+    }
+
+    /// Read the target of the symlink entry `e` at (normalized, resolved) `path`.  Targets longer than
+    /// `MAX_SYMLINK_TARGET` are rejected before decompressing anything, so a hostile archive can't make us buffer gigabytes.
+    fn read_symlink(&self, path: &str, e: &FileEntry) -> VfsResult<String> {
+        const MAX_SYMLINK_TARGET : u64 = 4096; // same as Linux's PATH_MAX
+        if e.uncompressed > MAX_SYMLINK_TARGET {
+            return Err(VfsError::IoError(io::Error::new(io::ErrorKind::InvalidData, format!("symlink target of \"{}\" is too long", path))));
         }
-        Ok(path)
+        let mut target = String::new();
+        self.open_entry(path, e)?.take(MAX_SYMLINK_TARGET).read_to_string(&mut target)?;
+        Ok(target)
     }
 
-    fn normalize_path_dir<'s>(&self, orig: &'s str) -> VfsResult<(&'s str, bool)> {
-        if orig == "" || orig == "/" {
-            Ok(("", true)) // root dir
-        } else if orig.ends_with('/') {
-            Ok((self.normalize_file(&orig[..orig.len()-1])?, true))
+    /// Open the file entry `e` at (normalized, resolved) `path`
+    fn open_entry(&self, path: &str, e: &FileEntry) -> VfsResult<Box<dyn SeekAndRead>> {
+        let local       = read_local_header(&self.io, e.header_offset)?;
+        let flags       = local.flags;
+        let encrypted   = flags & 0b0001 != 0;
+
+        let encryption_header = if encrypted { 12 } else { 0 };
+        if e.compression == zip::CompressionMethod::Stored && e.aes.is_none() && e.uncompressed.checked_add(encryption_header) != Some(e.compressed) {
+            return Err(VfsError::IoError(io::Error::new(io::ErrorKind::InvalidData, "Supposedly uncompressed file has different compressed vs uncompressed sizes")));
+        }
+
+        let compressed_start    = e.header_offset.checked_add(local.size());
+        let compressed_end      = compressed_start.and_then(|start| start.checked_add(e.compressed));
+        let (compressed_start, compressed_end) = match (compressed_start, compressed_end) {
+            (Some(start), Some(end)) => (start, end),
+            _ => return Err(VfsError::IoError(io::Error::new(io::ErrorKind::InvalidData, "file entry extends past the end of any possible zip archive"))),
+        };
+        let raw = ReadRange::new(ReadAtCursor::new(self.io.clone(), std::u64::MAX), compressed_start .. compressed_end);
+
+        let password = || self.options.password.as_ref().and_then(|cb| (cb.0)(path)).ok_or_else(||
+            VfsError::IoError(io::Error::new(io::ErrorKind::PermissionDenied, "encrypted file entry requires a password (see ZipReadOnlyOptions::password)"))
+        );
+
+        let file = match e.aes {
+            #[cfg(feature = "zip-aes")] Some(aes) => {
+                let raw = AesDecrypt::new(raw, &password()?[..], aes.strength, e.compressed)?;
+                self.decompress(path, e, &local, raw)?
+            },
+            #[cfg(not(feature = "zip-aes"))] Some(_) => {
+                return Err(VfsError::IoError(io::Error::new(io::ErrorKind::InvalidData, "vfs-zip requires the zip-aes feature to decrypt AES encrypted file entries")));
+            },
+            None if encrypted => {
+                // Bit 3 (sizes and CRC in a data descriptor after the file data) means the CRC wasn't known while writing the encryption header
+                let check = if flags & 0b1000 != 0 { (local.dos_time >> 8) as u8 } else { (e.crc32 >> 24) as u8 };
+                let raw = ZipCrypto::new(raw, &password()?[..], check, e.compressed.saturating_sub(encryption_header))?;
+                self.decompress(path, e, &local, raw)?
+            },
+            None => self.decompress(path, e, &local, raw)?,
+        };
+
+        let ae2 = e.aes.map_or(false, |aes| aes.version == 2); // AE-2 omits the CRC in favor of the authentication code
+        if self.options.verify_crc32 && !ae2 {
+            Ok(Box::new(Crc32Check::new(file, e.crc32, e.uncompressed)))
         } else {
-            Ok((self.normalize_file(orig)?, false))
+            Ok(file)
         }
     }
+
+    /// Decompress `raw` (the file's decrypted compressed data) into something seekable
+    fn decompress<R: Read + io::Seek + 'static>(&self, path: &str, e: &FileEntry, local: &LocalHeader, mut raw: R) -> VfsResult<Box<dyn SeekAndRead>> {
+        let prefer_in_memory    = self.options.limit_prefer_in_memory;
+        let allow_in_memory     = self.options.limit_allow_in_memory;
+
+        Ok(match e.compression {
+            zip::CompressionMethod::Stored if e.uncompressed <= prefer_in_memory => {
+                // Read decompressed data directly into a memory blob without an extra "compressed" copy
+
+                let unc = e.uncompressed.try_into().map_err(|_|
+                    VfsError::IoError(io::Error::new(io::ErrorKind::InvalidData, "vfs-zip must read file entry into memory, but it is too large"))
+                )?;
+                let mut unc = vec![0; unc];
+                raw.read_exact(&mut unc[..])?;
+                Box::new(std::io::Cursor::new(unc))
+            },
+            zip::CompressionMethod::Stored => {
+                // Read decompressed data directly from disk
+                Box::new(raw)
+            },
+            #[cfg(feature = "zip-deflate")] zip::CompressionMethod::Deflated if e.uncompressed > prefer_in_memory || local.flags & 0b0001 != 0 => {
+                let index = self.inflate_index(e.header_offset);
+                Box::new(SeekableDeflate::new(raw, e.uncompressed, index))
+            },
+            #[cfg(feature = "zip-deflate64")] zip::CompressionMethod::DEFLATE64 => {
+                let index = self.inflate_index(e.header_offset);
+                Box::new(Inflate::new_deflate64(raw, e.uncompressed, index))
+            },
+            #[cfg(feature = "zip-bzip2")] zip::CompressionMethod::Bzip2 if e.uncompressed > prefer_in_memory || local.flags & 0b0001 != 0 => {
+                let index = self.bzip2_index(e.header_offset);
+                Box::new(Bzip2Blocks::new(raw, e.uncompressed, index))
+            },
+            #[cfg(feature = "zip-zstd")] zip::CompressionMethod::ZSTD => {
+                let zstd = zstd::stream::read::Decoder::new(raw)?;
+                forward_only_or_in_memory(zstd, e.uncompressed, prefer_in_memory, "zstd")?
+            },
+            #[cfg(feature = "zip-lzma")] zip::CompressionMethod::LZMA => {
+                // ZIP's LZMA header: u8 major version, u8 minor version, u16 properties size, properties
+                let mut header = [0u8; 4 + 5];
+                raw.read_exact(&mut header[..])?;
+                if header[2..4] != [5, 0] { return Err(VfsError::IoError(io::Error::new(io::ErrorKind::InvalidData, "unexpected LZMA properties size"))); }
+
+                // General purpose bit 1: an end-of-stream marker terminates the data instead of relying on the uncompressed size
+                let eos_marker = local.flags & 0b0010 != 0;
+
+                // Rewrite as an .lzma ("LZMA alone") header: properties, u64 uncompressed size (or !0 if unknown)
+                let mut alone = header[4..].to_vec();
+                alone.extend_from_slice(&(if eos_marker { !0 } else { e.uncompressed }).to_le_bytes());
+
+                let stream = xz2::stream::Stream::new_lzma_decoder(std::u64::MAX).map_err(|e| VfsError::IoError(e.into()))?;
+                let lzma = xz2::read::XzDecoder::new_stream(std::io::Cursor::new(alone).chain(raw), stream);
+                forward_only_or_in_memory(lzma, e.uncompressed, prefer_in_memory, "lzma")?
+            },
+            #[cfg(feature = "zip-lzma")] zip::CompressionMethod::XZ => {
+                let xz = xz2::read::XzDecoder::new(raw);
+                forward_only_or_in_memory(xz, e.uncompressed, prefer_in_memory, "xz")?
+            },
+            _ if e.compressed   >= allow_in_memory => return Err(VfsError::Other { message: "compressed file exceeds allow_in_memory_limit but streaming this compression type from disk is not supported".into() }),
+            _ if e.uncompressed >= allow_in_memory => return Err(VfsError::Other { message: "uncompressed file exceeds allow_in_memory_limit but streaming this compression type from disk is not supported".into() }),
+            _ => { // Fallback: read compressed blob entirely into memory, and then decompressed blob into memory, and then return that.
+                use io::ErrorKind::InvalidData;
+
+                // Header + Compressed blob
+                let hacn = e.compressed.checked_add(local.size()).and_then(|n| n.try_into().ok()).ok_or_else(||
+                    VfsError::IoError(io::Error::new(InvalidData, "vfs-zip must read compressed file entry into memory, but it is too large"))
+                )?;
+
+                let mut hac = vec![0; hacn];
+                self.io.read_exact_at(&mut hac[..], e.header_offset)?;
+                let mut hac = std::io::Cursor::new(hac);
+
+                // Uncompressed blob
+                let uncn = e.uncompressed.try_into().map_err(|_|
+                    VfsError::IoError(io::Error::new(InvalidData, "vfs-zip must read decompressed file entry into memory, but it is too large"))
+                )?;
+                let mut unc = vec![0; uncn];
+                let mut zf = zip::read::read_zipfile_from_stream(&mut hac).map_err(|e| error::zip2vfs(path, e))?.ok_or_else(||
+                    VfsError::IoError(io::Error::new(InvalidData, "expected a file entry, did file contents change underneath this reader?!?"))
+                )?;
+                zf.read_exact(&mut unc[..])?;
+
+                Box::new(std::io::Cursor::new(unc))
+            }
+        })
+    }
 }
 
 impl<IO: Clone + ReadAt + Send + Sync + 'static> FileSystem for ZipReadOnly<IO> {
     fn read_dir(&self, orig: &str) -> VfsResult<Box<dyn Iterator<Item = String>>> {
-        let path = self.normalize_path_dir(orig)?.0;
-        if let Some(dir) = self.dirs.get(path) {
-            Ok(Box::new(dir.iter().cloned().collect::<Vec<_>>().into_iter())) // Eww
-        } else if let Some(_file) = self.files.get(path) {
+        let path = self.resolve(self.normalize_path_dir(orig)?.0)?;
+        let path = &*path;
+        if let Some(children) = self.dir_children(path) {
+            Ok(Box::new(children.into_iter()))
+        } else if self.file(path).is_some() {
             Err(VfsError::Other { message: format!("\"{}\" is a file, not a directory", orig) })
         } else {
             Err(VfsError::FileNotFound { path: orig.into() })
@@ -52,78 +237,11 @@ impl<IO: Clone + ReadAt + Send + Sync + 'static> FileSystem for ZipReadOnly<IO>
     }
 
     fn open_file(&self, orig: &str) -> VfsResult<Box<dyn SeekAndRead>> {
-        let path = self.normalize_file(orig)?;
-        if let Some(e) = self.files.get(path) {
-            if e.compression == zip::CompressionMethod::Stored && e.compressed != e.uncompressed {
-                return Err(VfsError::IoError(io::Error::new(io::ErrorKind::InvalidData, "Supposedly uncompressed file has different compressed vs uncompressed sizes")));
-            }
-
-            let compressed_start    = e.header_offset + e.header_size;
-            let compressed_end      = e.compressed + compressed_start;
-            let compressed          = compressed_start .. compressed_end;
-
-            match e.compression {
-                zip::CompressionMethod::Stored if e.uncompressed <= LIMIT_PREFER_IN_MEMORY => {
-                    // Read decompressed data directly into a memory blob without an extra "compressed" copy
-                    // TODO: CRC32 check?
-
-                    let unc = e.uncompressed as usize; // e.uncompressed <= LIMIT_PREFER_IN_MEMORY <= std::usize::MAX
-                    let mut unc = vec![0; unc];
-                    self.io.read_exact_at(&mut unc[..], compressed_start)?;
-                    Ok(Box::new(std::io::Cursor::new(unc)))
-                },
-                zip::CompressionMethod::Stored => {
-                    // Read decompressed data directly from disk
-                    // TODO: CRC32 check at EOF if read linearly?
-
-                    let rac = ReadAtCursor::new(self.io.clone(), std::u64::MAX);
-                    let rr = ReadRange::new(rac, compressed);
-                    Ok(Box::new(rr))
-                },
-                #[cfg(feature = "zip-deflate")] zip::CompressionMethod::Deflated if e.uncompressed > LIMIT_PREFER_IN_MEMORY => {
-                    use flate2::read::DeflateDecoder;
-                    struct Deflate<IO: Clone + ReadAt>(DeflateDecoder<ReadRange<ReadAtCursor<IO>>>);
-                    impl<IO: Clone + ReadAt> Read for Deflate<IO> { fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> { self.0.read(buf) } }
-                    impl<IO: Clone + ReadAt> Seek for Deflate<IO> { fn seek(&mut self, _: SeekFrom) -> io::Result<u64> { Err(io::Error::new(io::ErrorKind::Other, "Cannot seek within a deflate stream")) } }
-                    Ok(Box::new(Deflate(DeflateDecoder::new(ReadRange::new(ReadAtCursor::new(self.io.clone(), std::u64::MAX), compressed)))))
-                },
-                #[cfg(feature = "zip-bzip2")] zip::CompressionMethod::Bzip2 if e.uncompressed > LIMIT_PREFER_IN_MEMORY => {
-                    use bzip2::read::BzDecoder;
-                    struct Bz<IO: Clone + ReadAt>(BzDecoder<ReadRange<ReadAtCursor<IO>>>);
-                    impl<IO: Clone + ReadAt> Read for Bz<IO> { fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> { self.0.read(buf) } }
-                    impl<IO: Clone + ReadAt> Seek for Bz<IO> { fn seek(&mut self, _: SeekFrom) -> io::Result<u64> { Err(io::Error::new(io::ErrorKind::Other, "Cannot seek within a deflate stream")) } }
-                    Ok(Box::new(Bz(BzDecoder::new(ReadRange::new(ReadAtCursor::new(self.io.clone(), std::u64::MAX), compressed)))))
-                },
-                _ if e.compressed   >= LIMIT_ALLOW_IN_MEMORY => Err(VfsError::Other { message: "compressed file exceeds LIMIT_ALLOW_IN_MEMORY but streaming this compression type from disk is not supported".into() }),
-                _ if e.uncompressed >= LIMIT_ALLOW_IN_MEMORY => Err(VfsError::Other { message: "uncompressed file exceeds LIMIT_ALLOW_IN_MEMORY but streaming this compression type from disk is not supported".into() }),
-                _ => { // Fallback: read compressed blob entirely into memory, and then decompressed blob into memory, and then return that.
-                    use io::ErrorKind::InvalidData;
-
-                    // Header + Compressed blob
-                    let hacn = e.compressed.checked_add(e.header_size).and_then(|n| n.try_into().ok()).ok_or_else(||
-                        VfsError::IoError(io::Error::new(InvalidData, "vfs-zip must read compressed file entry into memory, but it is too large"))
-                    )?;
-
-                    let mut hac = Vec::new();
-                    hac.resize(hacn, 0);
-                    self.io.read_exact_at(&mut hac[..], e.header_offset)?;
-                    let mut hac = std::io::Cursor::new(hac);
-
-                    // Uncompressed blob
-                    let uncn = e.uncompressed.try_into().map_err(|_|
-                        VfsError::IoError(io::Error::new(InvalidData, "vfs-zip must read decompressed file entry into memory, but it is too large"))
-                    )?;
-                    let mut unc = Vec::new();
-                    unc.resize(uncn, 0);
-                    let mut zf = zip::read::read_zipfile_from_stream(&mut hac).map_err(|e| error::zip2vfs(&path, e))?.ok_or_else(||
-                        VfsError::IoError(io::Error::new(InvalidData, "expected a file entry, did file contents change underneath this reader?!?"))
-                    )?;
-                    zf.read_exact(&mut unc[..])?;
-
-                    Ok(Box::new(std::io::Cursor::new(unc)))
-                }
-            }
-        } else if let Some(_) = self.dirs.get(path) {
+        let path = self.resolve(self.normalize_file(orig)?)?;
+        let path = &*path;
+        if let Some(e) = self.file(path) {
+            self.open_entry(path, &e)
+        } else if self.is_dir(path) {
             Err(VfsError::Other { message: format!("\"{}\" is a directory, not a file", orig) })
         } else {
             Err(VfsError::FileNotFound { path: orig.into() })
@@ -132,9 +250,11 @@ impl<IO: Clone + ReadAt + Send + Sync + 'static> FileSystem for ZipReadOnly<IO>
 
     fn metadata(&self, orig: &str) -> VfsResult<VfsMetadata> {
         let (path, dir) = self.normalize_path_dir(orig)?;
-        if let Some(e) = self.files.get(path).filter(|_| !dir) {
+        let path = self.resolve(path)?;
+        let path = &*path;
+        if let Some(e) = self.file(path).filter(|_| !dir) {
             Ok(VfsMetadata { file_type: VfsFileType::File, len: e.uncompressed })
-        } else if let Some(_) = self.dirs.get(path) {
+        } else if self.is_dir(path) {
             Ok(VfsMetadata { file_type: VfsFileType::Directory, len: 0 })
         } else {
             Err(VfsError::FileNotFound { path: orig.into() })
@@ -146,7 +266,12 @@ impl<IO: Clone + ReadAt + Send + Sync + 'static> FileSystem for ZipReadOnly<IO>
             Ok(pd)  => pd,
             Err(_)  => return false, // XXX
         };
-        (!dir && self.files.contains_key(path)) || self.dirs.contains_key(path.trim_end_matches('/'))
+        let path = match self.resolve(path) {
+            Ok(path) => path,
+            Err(_)  => return false,
+        };
+        let path = &*path;
+        (!dir && self.file(path).is_some()) || self.is_dir(path.trim_end_matches('/'))
     }
 
     // these all involve writing, which zip::read::ZipArchive doesn't support
@@ -160,8 +285,26 @@ impl<IO: Clone + ReadAt + Send + Sync + 'static> FileSystem for ZipReadOnly<IO>
     fn move_dir     (&self, _src: &str, _dst: &str) -> VfsResult<()>                { Err(VfsError::NotSupported) }
 }
 
+fn escapes_root(path: &str) -> VfsError {
+    VfsError::IoError(io::Error::new(io::ErrorKind::InvalidData, format!("symlink escapes the archive root resolving \"{}\"", path)))
+}
+
+/// Streams that can't seek backwards are only used for large files - small files are decompressed into memory up front.
+#[allow(dead_code)] // only used by some features
+fn forward_only_or_in_memory(mut stream: impl Read + 'static, length: u64, prefer_in_memory: u64, what: &'static str) -> io::Result<Box<dyn SeekAndRead>> {
+    if length <= prefer_in_memory {
+        let mut unc = Vec::new();
+        stream.read_to_end(&mut unc)?;
+        Ok(Box::new(std::io::Cursor::new(unc)))
+    } else {
+        Ok(Box::new(ForwardOnly::new(stream, length, what)))
+    }
+}
+
 #[cfg(test)] mod tests {
     use super::*;
+    use crate::{IntoCloneReadAtLen, MultiVolume, ZipDuplicatePolicy, ZipReadOnlyOptions, ZipWarning, ZipWarningKind};
+    use std::io::{Seek, SeekFrom};
     use std::fs::File;
     use std::sync::{Arc, Mutex};
 
@@ -184,6 +327,920 @@ impl<IO: Clone + ReadAt + Send + Sync + 'static> FileSystem for ZipReadOnly<IO>
         with_zip("&'static [u8]",   files.iter().cloned(), dirs.iter().cloned(), &ZipReadOnly::new_strict(Box::leak(Box::<[u8]>::from(std::fs::read("test/data/early-vfs-zip.zip").unwrap()))).unwrap());
         // XXX: vfs04::FileSystem demands 'static which outlives a &[u8] slice
         let _ = ZipReadOnly::new_strict(&std::fs::read("test/data/early-vfs-zip.zip").unwrap()[..]).unwrap();
+
+        let never_in_memory     = ZipReadOnlyOptions::strict().prefer_in_memory_limit(0);
+        let always_in_memory    = ZipReadOnlyOptions::strict().prefer_in_memory_limit(std::u64::MAX);
+        with_zip("File (never in memory)",  files.iter().cloned(), dirs.iter().cloned(), &ZipReadOnly::new_with_options(File::open("test/data/early-vfs-zip.zip").unwrap(), never_in_memory).unwrap());
+        with_zip("File (always in memory)", files.iter().cloned(), dirs.iter().cloned(), &ZipReadOnly::new_with_options(File::open("test/data/early-vfs-zip.zip").unwrap(), always_in_memory).unwrap());
+        with_zip("File (lazy index)",       files.iter().cloned(), dirs.iter().cloned(), &ZipReadOnly::new_with_options(File::open("test/data/early-vfs-zip.zip").unwrap(), ZipReadOnlyOptions::strict().lazy_index(true)).unwrap());
+    }
+
+    #[test] fn crc32_mismatch() {
+        let mut zip = std::fs::read("test/data/early-vfs-zip.zip").unwrap();
+        let zro = ZipReadOnly::new_strict(&zip[..]).unwrap();
+        let e = &zro.files[".gitignore"];
+        let data_start = (e.header_offset + read_local_header(&zro.io, e.header_offset).unwrap().size()) as usize;
+        zip[data_start] ^= 0xFF;
+
+        for &prefer_in_memory in &[0, std::u64::MAX] {
+            let options = ZipReadOnlyOptions::strict().prefer_in_memory_limit(prefer_in_memory);
+
+            let verified = ZipReadOnly::new_with_options(zip.clone(), options.clone()).unwrap();
+            let err = verified.open_file(".gitignore").unwrap().read_to_end(&mut Vec::new()).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+            let unverified = ZipReadOnly::new_with_options(zip.clone(), options.verify_crc32(false)).unwrap();
+            unverified.open_file(".gitignore").unwrap().read_to_end(&mut Vec::new()).unwrap();
+        }
+    }
+
+    #[cfg(feature = "zip-deflate")] #[test] fn seek_deflate() { seek_compressed(zip::CompressionMethod::Deflated) }
+    #[cfg(feature = "zip-bzip2")]   #[test] fn seek_bzip2()   { seek_compressed(zip::CompressionMethod::Bzip2) }
+
+    #[allow(dead_code)] fn seek_compressed(method: zip::CompressionMethod) {
+        let mut text = Vec::new();
+        let mut rng = 42u32;
+        while text.len() < 2 * 1024 * 1024 {
+            rng = rng.wrapping_mul(1103515245).wrapping_add(12345);
+            text.extend_from_slice(["lorem ", "ipsum ", "dolor ", "sit ", "amet\n"][(rng >> 16) as usize % 5].as_bytes());
+            text.push(rng as u8);
+        }
+
+        let mut zip = zip::write::ZipWriter::new(io::Cursor::new(Vec::new()));
+        zip.start_file("text.txt", zip::write::FileOptions::default().compression_method(method)).unwrap();
+        zip.write_all(&text[..]).unwrap();
+        let zip = zip.finish().unwrap().into_inner();
+
+        for &interval in &[0, 64 * 1024] {
+            let options = ZipReadOnlyOptions::strict().deflate_checkpoint_interval(interval);
+            let zip = ZipReadOnly::new_with_options(zip.clone(), options).unwrap();
+
+            let mut a = zip.open_file("text.txt").unwrap();
+            let mut b = zip.open_file("text.txt").unwrap();
+            let mut all = Vec::new();
+            a.read_to_end(&mut all).unwrap();
+            assert!(all == text);
+
+            for &(seek, len) in &[(SeekFrom::Start(1000), 100), (SeekFrom::End(-5000), 5000), (SeekFrom::Start(1_500_000), 70_000), (SeekFrom::Start(10), 1)] {
+                for f in [&mut a, &mut b].iter_mut() {
+                    let pos = f.seek(seek).unwrap() as usize;
+                    let mut buf = vec![0; len];
+                    f.read_exact(&mut buf[..]).unwrap();
+                    assert!(buf[..] == text[pos..pos+len]);
+                }
+            }
+
+            // checkpoints are only kept while the file is open
+            drop((a, b));
+            let _c = zip.open_file("text.txt").unwrap();
+            assert!(zip.inflate_indices.lock().unwrap().len() <= 1);
+        }
+    }
+
+    #[cfg(feature = "zip-bzip2")] #[test] fn bzip2_byte_aligned_block() {
+        // find contents whose (only) bzip2 block ends on a byte boundary: the end of stream magic starts 10 bytes from the end
+        let (text, compressed) = (0..).map(|n: u32| {
+            let text = format!("{} bottles of beer on the wall\n", n).repeat(n as usize % 7 + 1);
+            let mut bz = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::Best);
+            bz.write_all(text.as_bytes()).unwrap();
+            (text, bz.finish().unwrap())
+        }).find(|(_, c)| c[c.len()-10 .. c.len()-4] == [0x17, 0x72, 0x45, 0x38, 0x50, 0x90]).unwrap();
+
+        let zip = raw_zip(&[RawFile { method: 12, compressed: &compressed[..], ..RawFile::stored("beer.txt", &text) }]);
+        let zip = ZipReadOnly::new_with_options(zip, ZipReadOnlyOptions::strict().prefer_in_memory_limit(0)).unwrap();
+        let mut read = String::new();
+        zip.open_file("beer.txt").unwrap().read_to_string(&mut read).unwrap();
+        assert_eq!(read, text);
+    }
+
+    #[cfg(feature = "zip-lzma")] #[test] fn lzma_and_xz() {
+        let text = "lorem ipsum dolor sit amet\n".repeat(1000);
+
+        let mut xz = Vec::new();
+        xz2::read::XzEncoder::new(text.as_bytes(), 6).read_to_end(&mut xz).unwrap();
+
+        let lzma_options = xz2::stream::LzmaOptions::new_preset(6).unwrap();
+        let mut alone = Vec::new();
+        xz2::read::XzEncoder::new_stream(text.as_bytes(), xz2::stream::Stream::new_lzma_encoder(&lzma_options).unwrap()).read_to_end(&mut alone).unwrap();
+        let mut lzma = vec![9, 20, 5, 0]; // LZMA SDK 9.20, 5 bytes of properties
+        lzma.extend_from_slice(&alone[..5]);
+        lzma.extend_from_slice(&alone[13..]); // skip .lzma's u64 uncompressed size
+
+        let zip = raw_zip(&[
+            RawFile { method: 95,                 compressed: &xz[..],   ..RawFile::stored("text.xz.txt",   &text) },
+            RawFile { method: 14, flags: 0b10,    compressed: &lzma[..], ..RawFile::stored("text.lzma.txt", &text) }, // .lzma always ends with an end-of-stream marker
+        ]);
+
+        for &prefer_in_memory in &[0, !0] {
+            let zip = ZipReadOnly::new_with_options(zip.clone(), ZipReadOnlyOptions::strict().prefer_in_memory_limit(prefer_in_memory)).unwrap();
+            for path in &["text.xz.txt", "text.lzma.txt"] {
+                let mut f = zip.open_file(path).unwrap();
+                if prefer_in_memory == 0 {
+                    assert_eq!(f.seek(SeekFrom::Start(27)).unwrap(), 27);
+                    let mut rest = String::new();
+                    f.read_to_string(&mut rest).unwrap();
+                    assert!(rest == text[27..]);
+                    assert!(f.seek(SeekFrom::Start(0)).is_err(), "forward only streams shouldn't be able to seek backwards");
+                } else {
+                    f.seek(SeekFrom::Start(27)).unwrap();
+                    f.seek(SeekFrom::Start(0)).unwrap();
+                    let mut all = String::new();
+                    f.read_to_string(&mut all).unwrap();
+                    assert!(all == text);
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "zip-deflate64")] #[test] fn deflate64() {
+        let mut text = Vec::new();
+        let mut rng = 42u32;
+        while text.len() < 60000 {
+            rng = rng.wrapping_mul(1103515245).wrapping_add(12345);
+            text.push((rng >> 16) as u8);
+        }
+
+        // No Deflate64 encoder handy, so build a stream by hand: a stored block, then a fixed huffman block copying
+        // 1000 bytes from 50153 bytes back - a length and a distance only Deflate64 can encode.
+        let mut bits : Vec<(u32, u32)> = vec![ // (value, count) LSB first
+            (0b000, 3), // not final, stored
+            (0, 5),     // align to byte
+            (text.len() as u32, 16),
+            (!text.len() as u32 & 0xFFFF, 16),
+        ];
+        for &b in text.iter() { bits.push((b as u32, 8)); }
+        let huffman = |code: u32, len: u32| ((0..len).fold(0, |rev, bit| rev << 1 | (code >> bit) & 1), len); // huffman codes are MSB first
+        bits.push((0b011, 3)); // final, fixed huffman
+        bits.push(huffman(0xC0 + 285 - 280, 8)); // length code 285
+        bits.push((1000 - 3, 16));
+        bits.push(huffman(31, 5)); // distance code 31
+        bits.push((50153 - 49153, 14));
+        bits.push(huffman(0, 7)); // end of block
+
+        let mut deflate64 = Vec::new();
+        let (mut acc, mut nbits) = (0u64, 0u32);
+        for &(value, count) in bits.iter() {
+            acc |= (value as u64) << nbits;
+            nbits += count;
+            while nbits >= 8 { deflate64.push(acc as u8); acc >>= 8; nbits -= 8; }
+        }
+        if nbits > 0 { deflate64.push(acc as u8); }
+
+        let start = text.len() - 50153;
+        let copy = text[start .. start + 1000].to_vec();
+        text.extend_from_slice(&copy[..]);
+
+        let zip = raw_zip(&[RawFile { method: 9, compressed: &deflate64[..], ..RawFile::stored("text.bin", &text[..]) }]);
+        let zip = ZipReadOnly::new_with_options(zip, ZipReadOnlyOptions::strict().deflate_checkpoint_interval(1)).unwrap();
+        let mut f = zip.open_file("text.bin").unwrap();
+        let mut all = Vec::new();
+        f.read_to_end(&mut all).unwrap();
+        assert!(all == text);
+
+        let pos = f.seek(SeekFrom::End(-500)).unwrap() as usize;
+        let mut end = Vec::new();
+        f.read_to_end(&mut end).unwrap();
+        assert!(end[..] == text[pos..]);
+    }
+
+    #[test] fn zip_crypto() {
+        let text = "lorem ipsum dolor sit amet\n".repeat(100);
+        let crc_check = (crc32fast::hash(text.as_bytes()) >> 24) as u8;
+
+        let encrypt = |password: &[u8], check: u8, plain: &[u8]| {
+            let mut keys = crate::ZipCryptoKeys::new(password);
+            let mut header = *b"random bytes";
+            header[11] = check;
+            header.iter().chain(plain.iter()).map(|&p| { let c = p ^ keys.stream_byte(); keys.update(p); c }).collect::<Vec<u8>>()
+        };
+
+        let stored      = encrypt(b"hunter2", crc_check, text.as_bytes());
+        let descriptor  = encrypt(b"hunter2", 0, text.as_bytes()); // data descriptor: check the high byte of the dos time instead
+        let other       = encrypt(b"letmein", crc_check, text.as_bytes());
+        let mut deflated = flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+        deflated.write_all(text.as_bytes()).unwrap();
+        let deflated = encrypt(b"hunter2", crc_check, &deflated.finish().unwrap()[..]);
+
+        let mut files = vec![
+            RawFile { flags: 0b0001, compressed: &stored[..],     ..RawFile::stored("stored.txt",     &text) },
+            RawFile { flags: 0b1001, compressed: &descriptor[..], ..RawFile::stored("descriptor.txt", &text) },
+            RawFile { flags: 0b0001, compressed: &other[..],      ..RawFile::stored("other.txt",      &text) },
+        ];
+        if cfg!(feature = "zip-deflate") {
+            files.push(RawFile { method: 8, flags: 0b0001, compressed: &deflated[..], ..RawFile::stored("deflated.txt", &text) });
+        }
+        let zip = raw_zip(&files[..]);
+
+        let permission_denied = |r: VfsResult<Box<dyn SeekAndRead>>| match r {
+            Err(VfsError::IoError(e)) => e.kind() == io::ErrorKind::PermissionDenied,
+            _ => false,
+        };
+
+        let no_password = ZipReadOnly::new_strict(zip.clone()).unwrap();
+        assert!(permission_denied(no_password.open_file("stored.txt")));
+
+        let options = ZipReadOnlyOptions::strict().password_callback(|path| Some(if path == "other.txt" { b"letmein".to_vec() } else { b"hunter2".to_vec() }));
+        let callback = ZipReadOnly::new_with_options(zip.clone(), options).unwrap();
+        let mut other = String::new();
+        callback.open_file("/other.txt").unwrap().read_to_string(&mut other).unwrap();
+        assert!(other == text);
+
+        let options = ZipReadOnlyOptions::strict().password("hunter2");
+        let zip = ZipReadOnly::new_with_options(zip.clone(), options).unwrap();
+        assert!(permission_denied(zip.open_file("other.txt")));
+        for file in files.iter().filter(|f| f.name != "other.txt") {
+            let mut f = zip.open_file(file.name).unwrap();
+            let mut all = String::new();
+            f.read_to_string(&mut all).unwrap();
+            assert!(all == text, "{}", file.name);
+
+            f.seek(SeekFrom::Start(27)).unwrap();
+            let mut line = [0u8; 27];
+            f.read_exact(&mut line[..]).unwrap();
+            assert!(line[..] == text.as_bytes()[..27], "{}", file.name);
+        }
+    }
+
+    #[test] fn modified_times() {
+        use std::time::{Duration, UNIX_EPOCH};
+
+        // NTFS extra field (100ns precision)
+        let zip = ZipReadOnly::new_strict(File::open("test/data/early-vfs-zip.zip").unwrap()).unwrap();
+        let gitignore = zip.zip_metadata("/.gitignore").unwrap();
+        assert!(gitignore.is_file());
+        assert_eq!(gitignore.modified(), Some(UNIX_EPOCH + Duration::new(1598967710, 43313200))); // 2020-09-01 13:41:50.0433132 UTC
+        assert_eq!(zip.zip_metadata(".vscode/").unwrap().modified(), None);
+        assert!(zip.zip_metadata("missing.txt").is_err());
+
+        let extended = [0x55, 0x54, 5, 0, 1, 0x00, 0xCA, 0x9A, 0x3B]; // Info-ZIP extended timestamp: mtime 1_000_000_000
+        let zip = raw_zip(&[
+            RawFile { extra: &extended[..], ..RawFile::stored("extended.txt", "") },
+            RawFile::stored("dos.txt", ""),
+        ]);
+        let zip = ZipReadOnly::new_strict(zip).unwrap();
+        assert_eq!(zip.zip_metadata("extended.txt").unwrap().modified(), Some(UNIX_EPOCH + Duration::from_secs(1_000_000_000)));
+        assert_eq!(zip.zip_metadata("dos.txt").unwrap().modified(), Some(UNIX_EPOCH + Duration::from_secs(315_532_800))); // 1980-01-01
+    }
+
+    #[test] fn attributes() {
+        let zip = ZipReadOnly::new_strict(File::open("test/data/early-vfs-zip.zip").unwrap()).unwrap();
+        let gitignore = zip.zip_metadata(".gitignore").unwrap();
+        assert_eq!(gitignore.host_system(), 0); // MS-DOS
+        assert_eq!(gitignore.external_attributes(), 0x20); // FILE_ATTRIBUTE_ARCHIVE
+        assert_eq!(gitignore.unix_mode(), None);
+        assert!(!gitignore.is_readonly() && !gitignore.is_hidden() && !gitignore.is_system());
+    }
+
+    #[test] fn symlinks() {
+        let link = |name, target| RawFile { unix_mode: 0o120777, ..RawFile::stored::<str>(name, target) };
+        let huge = "dir/".repeat(2000) + "file.txt";
+        let zip = raw_zip(&[
+            RawFile { unix_mode: 0o100644, ..RawFile::stored("dir/file.txt", "contents") },
+            link("file.lnk",        "dir/file.txt"),
+            link("dir/self.lnk",    "./file.txt"),
+            link("dir.lnk",         "dir"),
+            link("chain.lnk",       "dir.lnk/../file.lnk"),
+            link("escape.lnk",      "dir/../../etc/passwd"),
+            link("absolute.lnk",    "/etc/passwd"),
+            link("loop-a.lnk",      "loop-b.lnk"),
+            link("loop-b.lnk",      "loop-a.lnk"),
+            link("huge.lnk",        &huge),
+        ]);
+
+        let read = |zip: &dyn FileSystem, path: &str| -> VfsResult<String> {
+            let mut s = String::new();
+            zip.open_file(path)?.read_to_string(&mut s)?;
+            Ok(s)
+        };
+
+        let lstat = ZipReadOnly::new_strict(zip.clone()).unwrap();
+        assert_eq!(lstat.read_link("file.lnk").unwrap(), "dir/file.txt");
+        assert_eq!(lstat.read_link("/dir/self.lnk").unwrap(), "./file.txt");
+        assert!(lstat.read_link("dir/file.txt").is_err());
+        assert!(lstat.read_link("missing.lnk").is_err());
+        assert!(lstat.read_link("huge.lnk").is_err());
+        let meta = lstat.zip_metadata("file.lnk").unwrap();
+        assert!(meta.is_symlink() && !meta.is_file() && !meta.is_dir());
+        assert!(lstat.zip_metadata("dir/file.txt").unwrap().is_file());
+        assert_eq!(read(&lstat, "file.lnk").unwrap(), "dir/file.txt"); // not followed by default
+        assert!(!lstat.exists("dir.lnk/file.txt"));
+
+        let follow = ZipReadOnly::new_with_options(zip, ZipReadOnlyOptions::strict().follow_symlinks(true)).unwrap();
+        for path in &["file.lnk", "dir/self.lnk", "dir.lnk/file.txt", "dir.lnk/self.lnk", "chain.lnk"] {
+            assert_eq!(read(&follow, path).unwrap(), "contents", "{}", path);
+            assert_eq!(follow.metadata(path).unwrap().len, 8, "{}", path);
+            assert!(follow.exists(path), "{}", path);
+        }
+        assert_eq!(follow.read_dir("dir.lnk").unwrap().count(), 2);
+        assert!(follow.metadata("dir.lnk").unwrap().file_type == VfsFileType::Directory);
+        assert_eq!(follow.read_link("file.lnk").unwrap(), "dir/file.txt");
+
+        for &(path, kind, message) in &[
+            ("escape.lnk",      io::ErrorKind::InvalidData, "symlink escapes the archive root"),
+            ("absolute.lnk",    io::ErrorKind::InvalidData, "symlink escapes the archive root"),
+            ("loop-a.lnk",      io::ErrorKind::Other,       "too many levels of symlinks"),
+            ("huge.lnk",        io::ErrorKind::InvalidData, "symlink target of \"huge.lnk\" is too long"),
+        ] {
+            match follow.open_file(path) {
+                Err(VfsError::IoError(e)) => {
+                    assert_eq!(e.kind(), kind, "{}: {}", path, e);
+                    assert!(e.to_string().starts_with(message), "{}: {}", path, e);
+                },
+                Err(e) => panic!("{}: unexpected error {}", path, e),
+                Ok(_) => panic!("{}: expected an error", path),
+            }
+            assert!(!follow.exists(path), "{}", path);
+        }
+    }
+
+    #[test] fn case_insensitive() {
+        let file = |name: &'static str| RawFile { flags: if name.is_ascii() { 0 } else { 1 << 11 }, ..RawFile::stored(name, name) };
+        let files = [file("textures/foo.png"), file("Textures/Bar.png"), file("σοφος.txt"), file("docs/README.md"), file("docs/readme.md")];
+        let zip = raw_zip(&files[..]);
+        let options = ZipReadOnlyOptions::strict().case_insensitive(true);
+        assert!(ZipReadOnly::new_with_options(zip.clone(), options.clone()).is_err()); // README.md vs readme.md
+        let sensitive = ZipReadOnly::new_relaxed(zip.clone()).unwrap();
+        assert!(!sensitive.exists("Textures/Foo.PNG"));
+
+        let zip = ZipReadOnly::new_with_options(zip, options.ignore_file_errors(true)).unwrap();
+        let read = |path: &str| { let mut s = String::new(); zip.open_file(path).unwrap().read_to_string(&mut s).unwrap(); s };
+        assert_eq!(read("Textures/Foo.PNG"), "textures/foo.png");
+        assert_eq!(read("/TEXTURES/bar.PNG"), "Textures/Bar.png");
+        assert_eq!(read("ΣΟΦΟΣ.TXT"), "σοφος.txt");
+        assert_eq!(read("docs/Readme.md"), "docs/README.md"); // first wins
+        assert!(zip.exists("TEXTURES/") && zip.exists("Docs/README.MD") && !zip.exists("docs/readme.txt"));
+        assert_eq!(zip.metadata("textures/FOO.png").unwrap().len, 16);
+        assert_eq!(zip.zip_metadata("textures/FOO.png").unwrap().len(), 16);
+
+        let mut textures = zip.read_dir("Textures").unwrap().collect::<Vec<_>>();
+        textures.sort();
+        assert_eq!(textures, vec!["Bar.png", "foo.png"]);
+        assert_eq!(zip.read_dir("docs").unwrap().collect::<Vec<_>>(), vec!["README.md"]);
+    }
+
+    #[test] fn backslash_separators() {
+        let file = |name: &'static str| RawFile::stored(name, name);
+        let zip = raw_zip(&[file("foo\\bar.txt"), file("foo/baz.txt"), file("empty\\")]);
+        assert!(ZipReadOnly::new_strict(zip.clone()).is_err());
+        assert!(!ZipReadOnly::new_relaxed(zip.clone()).unwrap().exists("foo/bar.txt")); // skipped
+
+        let zip = ZipReadOnly::new_with_options(zip, ZipReadOnlyOptions::strict().backslash_separators(true)).unwrap();
+        let mut bar = String::new();
+        zip.open_file("foo/bar.txt").unwrap().read_to_string(&mut bar).unwrap();
+        assert_eq!(bar, "foo\\bar.txt");
+        assert_eq!(zip.read_dir("foo").unwrap().count(), 2);
+        assert!(zip.metadata("empty").unwrap().file_type == VfsFileType::Directory);
+        assert_eq!(zip.zip_metadata("foo/bar.txt").unwrap().raw_name(), Some("foo\\bar.txt"));
+        assert_eq!(zip.zip_metadata("foo/baz.txt").unwrap().raw_name(), None);
+        assert_eq!(zip.zip_metadata("empty/").unwrap().raw_name(), Some("empty\\"));
+
+        let collision = raw_zip(&[file("foo/bar.txt"), file("foo\\bar.txt")]);
+        let options = ZipReadOnlyOptions::strict().backslash_separators(true);
+        assert!(ZipReadOnly::new_with_options(collision.clone(), options.clone()).is_err());
+        let zip = ZipReadOnly::new_with_options(collision, options.ignore_file_errors(true)).unwrap();
+        let mut bar = String::new();
+        zip.open_file("foo/bar.txt").unwrap().read_to_string(&mut bar).unwrap();
+        assert_eq!(bar, "foo/bar.txt");
+    }
+
+    #[test] fn warnings() {
+        let file = RawFile::stored::<str>;
+        let zip = raw_zip(&[file("a.txt", "old"), file("b\\c.txt", ""), file("d//e.txt", ""), file("a.txt", "new"), file("f.txt", "")]);
+
+        let last_wins = ZipReadOnlyOptions::strict().duplicates(ZipDuplicatePolicy::LastWins);
+        let last_wins = ZipReadOnly::new_with_options(raw_zip(&[file("a.txt", "old"), file("a.txt", "new")]), last_wins).unwrap();
+        assert_eq!(last_wins.warnings(), &[ZipWarning { index: 1, name: "a.txt".into(), kind: ZipWarningKind::Duplicate }]);
+
+        let relaxed = ZipReadOnly::new_relaxed(zip).unwrap();
+        let warnings = relaxed.warnings();
+        assert_eq!(warnings.iter().map(|w| (w.index(), w.name(), w.kind().clone())).collect::<Vec<_>>(), vec![
+            (1, "b\\c.txt", ZipWarningKind::Backslash),
+            (2, "d//e.txt", ZipWarningKind::EmptyComponent),
+            (3, "a.txt",    ZipWarningKind::Duplicate),
+        ]);
+        assert_eq!(warnings[1].to_string(), "entry #2 \"d//e.txt\": contains an empty path component");
+        assert!(relaxed.exists("f.txt"));
+        assert!(ZipReadOnly::new_relaxed(File::open("test/data/early-vfs-zip.zip").unwrap()).unwrap().warnings().is_empty());
+    }
+
+    #[test] fn duplicates() {
+        let file = RawFile::stored::<str>;
+        let zip = raw_zip(&[file("dir/a.txt", "first"), file("dir/b.txt", ""), file("dir/a.txt", "last")]);
+        let read = |zip: &dyn FileSystem| { let mut s = String::new(); zip.open_file("dir/a.txt").unwrap().read_to_string(&mut s).unwrap(); s };
+
+        assert!(ZipReadOnly::new_strict(zip.clone()).is_err());
+        assert_eq!(read(&ZipReadOnly::new_relaxed(zip.clone()).unwrap()), "first");
+        for &(policy, expected) in &[(ZipDuplicatePolicy::FirstWins, "first"), (ZipDuplicatePolicy::LastWins, "last")] {
+            let zip = ZipReadOnly::new_with_options(zip.clone(), ZipReadOnlyOptions::strict().duplicates(policy)).unwrap();
+            assert_eq!(read(&zip), expected);
+            assert_eq!(zip.read_dir("dir").unwrap().count(), 2);
+            assert_eq!(zip.metadata("dir/a.txt").unwrap().len, expected.len() as u64);
+            assert_eq!(zip.warnings().len(), 1);
+        }
+
+        // case insensitive duplicates keep the first spelling
+        let zip = raw_zip(&[file("A.txt", "first"), file("a.txt", "last")]);
+        let options = ZipReadOnlyOptions::strict().case_insensitive(true).duplicates(ZipDuplicatePolicy::LastWins);
+        let zip = ZipReadOnly::new_with_options(zip, options).unwrap();
+        assert_eq!(zip.read_dir("").unwrap().collect::<Vec<_>>(), vec!["A.txt"]);
+        assert_eq!(zip.metadata("a.txt").unwrap().len, 4);
+        assert_eq!(zip.warnings()[0].kind(), &ZipWarningKind::CaseCollision);
+    }
+
+    #[test] fn filename_encodings() {
+        let unicode_path = |raw: &[u8], name: &str| { // Info-ZIP Unicode Path extra field
+            let mut extra = vec![0x75, 0x70];
+            extra.extend_from_slice(&(5 + name.len() as u16).to_le_bytes());
+            extra.push(1);
+            extra.extend_from_slice(&crc32fast::hash(raw).to_le_bytes());
+            extra.extend_from_slice(name.as_bytes());
+            extra
+        };
+        let (sjis_a, sjis_i) = (b"\x82\xa0.txt", b"\x82\xa2.txt"); // Shift-JIS "あ.txt", "い.txt"
+        let good = unicode_path(sjis_a, "あ.txt");
+        let stale = unicode_path(b"other.txt", "stale.txt");
+        let file = |name, extra| RawFile { extra, ..RawFile::stored(name, "") };
+        let mut zip = raw_zip(&[file("A_.txt", &good[..]), file("I_.txt", &stale[..]), file("ü.txt", &[])]);
+        for &(placeholder, raw) in &[(&b"A_.txt"[..], &sjis_a[..]), (&b"I_.txt"[..], &sjis_i[..])] {
+            while let Some(i) = zip.windows(placeholder.len()).position(|w| w == placeholder) { zip[i..i+raw.len()].copy_from_slice(raw); }
+        }
+        let names = |zip: ZipReadOnly<_>| { let mut names = zip.read_dir("").unwrap().collect::<Vec<_>>(); names.sort(); names };
+
+        // default: CP437 (except for the valid Unicode Path extra field, and "ü.txt", which raw_zip didn't flag as UTF-8)
+        assert_eq!(names(ZipReadOnly::new_strict(zip.clone()).unwrap()), vec!["éó.txt", "├╝.txt", "あ.txt"]);
+
+        let upper = ZipReadOnlyOptions::strict().filename_decoder(|raw| String::from_utf8_lossy(raw).to_uppercase());
+        assert_eq!(names(ZipReadOnly::new_with_options(zip.clone(), upper).unwrap()), vec!["Ü.TXT", "あ.txt", "\u{FFFD}\u{FFFD}.TXT"]);
+
+        #[cfg(feature = "zip-encodings")] {
+            let sjis = ZipReadOnlyOptions::strict().filename_encoding(crate::encoding_rs::SHIFT_JIS);
+            let zip = ZipReadOnly::new_with_options(zip, sjis).unwrap();
+            assert!(zip.exists("い.txt") && zip.exists("あ.txt"));
+        }
+    }
+
+    #[cfg(feature = "unicode-nfc")] #[test] fn unicode_nfc() {
+        const UTF8 : u16 = 1 << 11;
+        let file = |name, contents| RawFile { flags: UTF8, ..RawFile::stored(name, contents) };
+        let nfd = raw_zip(&[file("cafe\u{301}/cre\u{300}me.txt", "nfd")]);
+
+        let zip = ZipReadOnly::new_strict(nfd.clone()).unwrap();
+        assert!(!zip.exists("caf\u{e9}/cr\u{e8}me.txt"));
+        assert!( zip.exists("cafe\u{301}/cre\u{300}me.txt"));
+
+        let options = || ZipReadOnlyOptions::strict().unicode_nfc(true);
+        let zip = ZipReadOnly::new_with_options(nfd, options()).unwrap();
+        assert_eq!(zip.read_dir("").unwrap().collect::<Vec<_>>(), vec!["caf\u{e9}"]);
+        assert!(zip.exists("caf\u{e9}/cr\u{e8}me.txt"));
+        assert!(zip.exists("cafe\u{301}/cre\u{300}me.txt"));
+        assert_eq!(zip.zip_metadata("cafe\u{301}/cr\u{e8}me.txt").unwrap().raw_name(), Some("cafe\u{301}/cre\u{300}me.txt"));
+
+        // NFC and NFD spellings of the same name are duplicates
+        let both = raw_zip(&[file("caf\u{e9}.txt", "nfc"), file("cafe\u{301}.txt", "nfd")]);
+        assert!(ZipReadOnly::new_strict(both.clone()).is_ok());
+        assert!(ZipReadOnly::new_with_options(both.clone(), options()).is_err());
+        let zip = ZipReadOnly::new_with_options(both, options().duplicates(ZipDuplicatePolicy::LastWins)).unwrap();
+        let mut s = String::new();
+        zip.open_file("cafe\u{301}.txt").unwrap().read_to_string(&mut s).unwrap();
+        assert_eq!(s, "nfd");
+        assert_eq!(zip.warnings()[0].kind(), &ZipWarningKind::NormalizationCollision);
+    }
+
+    #[test] fn central_directory() {
+        let file = RawFile::stored::<str>;
+        let zip = raw_zip(&[file("a.txt", "hello"), file("dir/b.txt", "world")]);
+        let read = |zip: &dyn FileSystem, path| { let mut s = String::new(); zip.open_file(path).unwrap().read_to_string(&mut s).unwrap(); s };
+
+        // prepended data (e.g. self extracting executables)
+        let mut sfx = b"MZ (not really an executable)".to_vec();
+        sfx.extend_from_slice(&zip[..]);
+        let sfx = ZipReadOnly::new_strict(sfx).unwrap();
+        assert_eq!(read(&sfx, "a.txt"), "hello");
+        assert_eq!(read(&sfx, "dir/b.txt"), "world");
+
+        // local headers are only read when opening their entry
+        let mut corrupt = zip.clone();
+        corrupt[0] = b'X';
+        let corrupt = ZipReadOnly::new_strict(corrupt).unwrap();
+        match corrupt.open_file("a.txt") {
+            Err(VfsError::IoError(err)) => assert_eq!(err.kind(), io::ErrorKind::InvalidData),
+            _                           => panic!("expected an InvalidData error"),
+        }
+        assert_eq!(read(&corrupt, "dir/b.txt"), "world");
+
+        // archive comments containing something that looks like an end of central directory record
+        let mut commented = zip.clone();
+        let comment = [&b"PK\x05\x06"[..], &[0; 18], b" (a zero-length comment, so not a valid end of central directory record)"].concat();
+        let eocd = commented.len() - 22;
+        commented[eocd + 20] = comment.len() as u8;
+        commented.extend_from_slice(&comment[..]);
+        let commented = ZipReadOnly::new_strict(commented).unwrap();
+        assert_eq!(commented.comment(), &comment[..]);
+        assert_eq!(read(&commented, "a.txt"), "hello");
+
+        // local header offsets that overflow once prepended data is accounted for
+        let zip64_offset = [&[1, 0, 8, 0][..], &std::u64::MAX.to_le_bytes()[..]].concat();
+        let mut overflow = b"MZ".to_vec();
+        overflow.extend_from_slice(&raw_zip(&[RawFile { extra: &zip64_offset[..], ..file("a.txt", "hello") }])[..]);
+        let central = overflow.windows(4).position(|w| w == b"PK\x01\x02").unwrap();
+        overflow[central + 42 .. central + 46].copy_from_slice(&[0xFF; 4]);
+        assert!(ZipReadOnly::new_strict(overflow).is_err());
+
+        // multi-disk archives
+        let mut multi_disk = zip.clone();
+        let eocd = multi_disk.len() - 22;
+        multi_disk[eocd + 4] = 1;
+        assert!(ZipReadOnly::new_strict(multi_disk).is_err());
+    }
+
+    #[test] fn zip64() {
+        const BIG : u64 = 0x1_0000_0010; // just over 4 GiB
+        let (chunks, len) = zip64_chunks(BIG);
+        let check = |what: &str, zip: &dyn FileSystem| {
+            assert_eq!(zip.metadata("big.bin").unwrap().len, BIG, "{}", what);
+            let mut big = zip.open_file("big.bin").unwrap();
+            let mut tail = Vec::new();
+            assert_eq!(big.seek(SeekFrom::End(-8)).unwrap(), BIG - 8, "{}", what);
+            big.read_to_end(&mut tail).unwrap();
+            assert_eq!(tail, b"big end!", "{}", what);
+            let mut zeros = [0xFFu8; 8];
+            big.seek(SeekFrom::Start(0xFFFF_FFFC)).unwrap();
+            big.read_exact(&mut zeros[..]).unwrap();
+            assert_eq!(zeros, [0u8; 8], "{}", what);
+
+            let mut small = String::new();
+            zip.open_file("small.txt").unwrap().read_to_string(&mut small).unwrap(); // after `big`: zip64 header offset
+            assert_eq!(small, "hello", "{}", what);
+        };
+
+        let sparse = Sparse { chunks: Arc::from(chunks), len };
+        check("ReadAt",     &ZipReadOnly::new_strict(sparse.clone()).unwrap());
+        check("Mutex",      &ZipReadOnly::new_strict(Mutex::new(ReadAtCursor::new(sparse.clone(), len))).unwrap());
+        check("Arc<Mutex>", &ZipReadOnly::new_strict(Arc::new(Mutex::new(ReadAtCursor::new(sparse.clone(), len)))).unwrap());
+
+        // prepended data: the zip64 end of central directory locator's offset is relative to the start of the zip
+        let prepended = Sparse { chunks: sparse.chunks.iter().map(|(o, c)| (o + 100, c.clone())).collect::<Vec<_>>().into(), len: len + 100 };
+        check("ReadAt (prepended data)", &ZipReadOnly::new_strict(prepended).unwrap());
+
+        // saturated disk numbers in the end of central directory record, which just mean "see the zip64 record"
+        let mut saturated = sparse.chunks.to_vec();
+        let tail = &mut saturated.last_mut().unwrap().1;
+        let eocd = tail.len() - 22;
+        tail[eocd + 4 .. eocd + 8].copy_from_slice(&[0xFF; 4]);
+        check("ReadAt (saturated disks)", &ZipReadOnly::new_strict(Sparse { chunks: saturated.into(), len }).unwrap());
+
+        // hostile zip64 sizes, which would overflow the end of big.bin's data
+        let mut hostile = sparse.chunks.to_vec();
+        let tail = &mut hostile.last_mut().unwrap().1;
+        let sizes = tail.windows(7).position(|w| w == b"big.bin").unwrap() + 7 + 4;
+        for size in tail[sizes .. sizes + 16].chunks_mut(8) { size.copy_from_slice(&(std::u64::MAX - 8).to_le_bytes()); }
+        let zip = ZipReadOnly::new_strict(Sparse { chunks: hostile.into(), len }).unwrap();
+        match zip.open_file("big.bin") {
+            Err(VfsError::IoError(e)) => assert_eq!(e.kind(), io::ErrorKind::InvalidData),
+            Err(e) => panic!("unexpected error {}", e),
+            Ok(_) => panic!("expected an error"),
+        }
+
+        #[cfg(unix)] { // sparse file (NTFS would need FSCTL_SET_SPARSE to avoid writing out gigabytes of zeros)
+            use std::io::Write;
+            let _ = std::fs::create_dir_all("target/tmp");
+            let path = "target/tmp/zip64.zip";
+            let mut file = File::create(path).unwrap();
+            file.set_len(len).unwrap();
+            for (offset, chunk) in sparse.chunks.iter() {
+                file.seek(SeekFrom::Start(*offset)).unwrap();
+                file.write_all(&chunk[..]).unwrap();
+            }
+            std::mem::drop(file);
+            check("File", &ZipReadOnly::new_strict(File::open(path).unwrap()).unwrap());
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    #[test] fn lazy_index() {
+        let file = RawFile::stored::<str>;
+        let mut zip = raw_zip(&[
+            file("b/x.txt", "x"), file("a.txt", "first"), file("b/", ""), file("b.txt", ""), file("b/c/d/e.txt", ""),
+            file("b0.txt", ""), file("\\back.txt", ""), file("/abs.txt", ""), file("a.txt", "last"), file("~.txt", ""),
+        ]);
+        while let Some(i) = zip.windows(5).position(|w| w == b"~.txt") { zip[i] = 0x82; } // "é.txt" in CP437
+        let lazy = || ZipReadOnlyOptions::relaxed().lazy_index(true);
+        let eager = ZipReadOnly::new_relaxed(zip.clone()).unwrap();
+        let zip_lazy = ZipReadOnly::new_with_options(zip.clone(), lazy()).unwrap();
+
+        // same results as the eager index
+        same_tree(&eager, &zip_lazy, "");
+        assert_eq!(zip_lazy.read_dir("").unwrap().collect::<Vec<_>>(), vec!["a.txt", "b", "b.txt", "b0.txt", "\u{e9}.txt"]);
+        let duplicates = eager.warnings().iter().filter(|w| w.kind() == &ZipWarningKind::Duplicate).cloned().collect::<Vec<_>>();
+        assert_eq!(zip_lazy.warnings(), &duplicates[..]); // invalid names are skipped without warnings when reached
+        assert!( zip_lazy.exists("b/c/d") && zip_lazy.exists("b/c/") && !zip_lazy.exists("b/c/d/e"));
+        assert!( zip_lazy.open_file("b").is_err() && zip_lazy.read_dir("a.txt").is_err() && zip_lazy.read_dir("b/x").is_err());
+        assert!( zip_lazy.zip_metadata("b").unwrap().is_dir);
+
+        // errors, duplicates, and unsupported options
+        assert!(ZipReadOnly::new_with_options(zip.clone(), lazy().ignore_file_errors(false)).is_err());
+        let strict = ZipReadOnly::new_with_options(zip.clone(), lazy().ignore_file_errors(false).duplicates(ZipDuplicatePolicy::FirstWins)).unwrap();
+        same_tree(&eager, &strict, "");
+        let last_wins = ZipReadOnly::new_with_options(zip.clone(), lazy().duplicates(ZipDuplicatePolicy::LastWins)).unwrap();
+        assert_eq!(last_wins.zip_metadata("a.txt").unwrap().len, 4);
+        assert!(ZipReadOnly::new_with_options(zip.clone(), lazy().case_insensitive(true)).is_err());
+        assert!(ZipReadOnly::new_with_options(zip, lazy().backslash_separators(true)).is_err());
+    }
+
+    #[test] fn index_cache() {
+        let _ = std::fs::create_dir_all("target/tmp");
+        let cache = "target/tmp/index_cache.idx";
+        let _ = std::fs::remove_file(cache);
+        let file = |name, contents| RawFile { unix_mode: 0o100644, ..RawFile::stored(name, contents) };
+        let zip = raw_zip(&[file("a.txt", "first"), file("b/", ""), file("b/c.txt", "c"), file("a.txt", "last"), file("/abs.txt", "")]);
+        let options = || ZipReadOnlyOptions::relaxed().keep_extra_fields(true);
+        let parsed = ZipReadOnly::new_with_options(zip.clone(), options()).unwrap();
+
+        // missing caches are built and written
+        let built = ZipReadOnly::new_with_index_cache(zip.clone(), options(), cache).unwrap();
+        assert!(std::path::Path::new(cache).exists());
+        same_tree(&parsed, &built, "");
+
+        // up to date caches are loaded instead of parsing the (here, corrupted) central directory
+        let mut corrupt = zip.clone();
+        let central = corrupt.windows(4).position(|w| w == b"PK\x01\x02").unwrap();
+        corrupt[central] = b'X';
+        assert!(ZipReadOnly::new_with_options(corrupt.clone(), options()).is_err());
+        let loaded = ZipReadOnly::new_with_index_cache(corrupt, options(), cache).unwrap();
+        same_tree(&parsed, &loaded, "");
+        assert_eq!(loaded.warnings(), parsed.warnings());
+        let (p, l) = (parsed.zip_metadata("a.txt").unwrap(), loaded.zip_metadata("a.txt").unwrap());
+        assert_eq!((p.modified, p.external_attributes, p.local_extra), (l.modified, l.external_attributes, l.local_extra));
+
+        // stale (different archive or options) and corrupt caches are rebuilt
+        let other = ZipReadOnly::new_with_index_cache(raw_zip(&[file("d.txt", "d")]), options(), cache).unwrap();
+        assert_eq!(other.read_dir("").unwrap().collect::<Vec<_>>(), vec!["d.txt"]);
+        assert!(ZipReadOnly::new_with_index_cache(zip.clone(), ZipReadOnlyOptions::strict(), cache).is_err()); // duplicates
+        std::fs::write(cache, b"vfszipix garbage").unwrap();
+        same_tree(&parsed, &ZipReadOnly::new_with_index_cache(zip.clone(), options(), cache).unwrap(), "");
+
+        // explicitly saved caches are loaded too, but lazy indices can't be cached
+        std::fs::remove_file(cache).unwrap();
+        parsed.save_index_cache(cache).unwrap();
+        let mut corrupt = zip.clone();
+        corrupt[central] = b'X';
+        same_tree(&parsed, &ZipReadOnly::new_with_index_cache(corrupt, options(), cache).unwrap(), "");
+        assert!(ZipReadOnly::new_with_index_cache(zip.clone(), options().lazy_index(true), cache).is_err());
+        assert!(ZipReadOnly::new_with_options(zip, options().lazy_index(true)).unwrap().save_index_cache(cache).is_err());
+    }
+
+    #[test] fn multi_volume() {
+        let file = RawFile::stored::<str>;
+        let zip = raw_zip(&[file("a.txt", "alpha"), file("b/c.txt", "charlie"), file("d.txt", "delta")]);
+        let single = ZipReadOnly::new_strict(zip.clone()).unwrap();
+
+        // split archives are just concatenated, so volumes can end anywhere (even mid header), or be empty
+        let split = MultiVolume::new(vec![zip[..10].to_vec(), zip[10..40].to_vec(), Vec::new(), zip[40..].to_vec()]).unwrap();
+        same_tree(&single, &ZipReadOnly::new_strict(split).unwrap(), "");
+
+        // spanned archives locate entries by disk number and offset within that disk's volume
+        let locals = zip.windows(4).enumerate().filter(|&(_, w)| w == b"PK\x03\x04").map(|(i, _)| i + 4).collect::<Vec<_>>(); // after the spanning signature
+        let volumes = spanned_zip(&zip, &[locals[1], locals[2] + 12]); // b/c.txt starts disk 1, d.txt spans disks 1 and 2
+        same_tree(&single, &ZipReadOnly::new_strict(MultiVolume::new(volumes.clone()).unwrap()).unwrap(), "");
+        same_tree(&single, &ZipReadOnly::new_with_options(MultiVolume::new(volumes.clone()).unwrap(), ZipReadOnlyOptions::strict().lazy_index(true)).unwrap(), "");
+        assert!(ZipReadOnly::new_strict(volumes[2].clone()).is_err());
+        assert!(ZipReadOnly::new_strict(MultiVolume::new(volumes[1..].to_vec()).unwrap()).is_err());
+
+        // a zip64 end of central directory record can't claim a central directory larger than the archive
+        let with_zip64 = |cd_size: u64| {
+            let mut last = volumes[2].clone();
+            let eocd = last.len() - 22;
+            let u16at = |i: usize| u16::from_le_bytes([last[i], last[i+1]]);
+            let u32at = |i: usize| u32::from_le_bytes([last[i], last[i+1], last[i+2], last[i+3]]);
+            let mut records = b"PK\x06\x06".to_vec();
+            records.extend_from_slice(&44u64.to_le_bytes()); // size of the remaining record
+            for &n in &[45u16, 45] { records.extend_from_slice(&n.to_le_bytes()); } // version made by, version needed
+            for &n in &[2, u16at(eocd + 6) as u32] { records.extend_from_slice(&n.to_le_bytes()); } // disk, central directory disk
+            for &n in &[u16at(eocd + 8) as u64, u16at(eocd + 10) as u64, cd_size, u32at(eocd + 16) as u64] { records.extend_from_slice(&n.to_le_bytes()); }
+            records.extend_from_slice(b"PK\x06\x07");
+            records.extend_from_slice(&2u32.to_le_bytes()); // disk
+            records.extend_from_slice(&(eocd as u64).to_le_bytes());
+            records.extend_from_slice(&3u32.to_le_bytes()); // total disks
+            last.splice(eocd..eocd, records);
+            ZipReadOnly::new_strict(MultiVolume::new(vec![volumes[0].clone(), volumes[1].clone(), last]).unwrap())
+        };
+        let eocd = volumes[2].len() - 22;
+        let cd_size = u32::from_le_bytes([volumes[2][eocd+12], volumes[2][eocd+13], volumes[2][eocd+14], volumes[2][eocd+15]]);
+        same_tree(&single, &with_zip64(cd_size as u64).unwrap(), "");
+        assert!(with_zip64(1 << 62).is_err()); // rather than attempting a 4 EiB allocation
+
+        // MultiVolume::open finds the numbered volumes preceding the .zip
+        let _ = std::fs::create_dir_all("target/tmp");
+        let _ = std::fs::remove_file("target/tmp/spanned.z03");
+        for (path, volume) in ["target/tmp/spanned.z01", "target/tmp/spanned.z02", "target/tmp/spanned.zip"].iter().zip(volumes.iter()) {
+            std::fs::write(path, volume).unwrap();
+        }
+        let opened = MultiVolume::open("target/tmp/spanned.zip").unwrap();
+        assert_eq!(opened.volumes(), 3);
+        same_tree(&single, &ZipReadOnly::new_strict(opened).unwrap(), "");
+    }
+
+    /// Assert that `expected` and `actual` have the same directories, file sizes, and file contents under `dir`
+    fn same_tree(expected: &dyn FileSystem, actual: &dyn FileSystem, dir: &str) {
+        let children = expected.read_dir(dir).unwrap().collect::<Vec<_>>();
+        assert_eq!(actual.read_dir(dir).unwrap().collect::<Vec<_>>(), children, "read_dir({:?})", dir);
+        for child in children {
+            let path = if dir.is_empty() { child } else { format!("{}/{}", dir, child) };
+            let (e, a) = (expected.metadata(&path).unwrap(), actual.metadata(&path).unwrap());
+            assert_eq!((e.file_type, e.len), (a.file_type, a.len), "metadata({:?})", path);
+            if e.file_type == VfsFileType::Directory {
+                same_tree(expected, actual, &path);
+            } else {
+                let (mut e, mut a) = (String::new(), String::new());
+                expected.open_file(&path).unwrap().read_to_string(&mut e).unwrap();
+                actual  .open_file(&path).unwrap().read_to_string(&mut a).unwrap();
+                assert_eq!(e, a, "open_file({:?})", path);
+            }
+        }
+    }
+
+    struct RawFile<'a> {
+        name:           &'a str,
+        method:         u16,
+        flags:          u16, // general purpose bit flags
+        uncompressed:   &'a [u8],
+        compressed:     &'a [u8],
+        extra:          &'a [u8], // central directory extra fields
+        unix_mode:      u32, // 0 for an MS-DOS host
+    }
+
+    impl<'a> RawFile<'a> {
+        /// An uncompressed, unencrypted file from an MS-DOS host.  Override other fields with struct update syntax.
+        fn stored<C: AsRef<[u8]> + ?Sized>(name: &'a str, contents: &'a C) -> Self {
+            let contents = contents.as_ref();
+            RawFile { name, method: 0, flags: 0, uncompressed: contents, compressed: contents, extra: &[], unix_mode: 0 }
+        }
+    }
+
+    /// Build a zip from already compressed files
+    fn raw_zip(files: &[RawFile]) -> Vec<u8> {
+        let mut zip = Vec::new();
+        let mut central = Vec::new();
+        for &RawFile { name, method, flags, uncompressed, compressed, extra, unix_mode } in files {
+            let offset = zip.len() as u32;
+            let mut common = Vec::new();
+            for &n in &[20u16, flags, method, 0, 0x21] { common.extend_from_slice(&n.to_le_bytes()); } // version needed, flags, method, dos time, dos date
+            common.extend_from_slice(&crc32fast::hash(uncompressed).to_le_bytes());
+            common.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+            common.extend_from_slice(&(uncompressed.len() as u32).to_le_bytes());
+            common.extend_from_slice(&(name.len() as u16).to_le_bytes());
+
+            zip.extend_from_slice(b"PK\x03\x04");
+            zip.extend_from_slice(&common[..]);
+            zip.extend_from_slice(&0u16.to_le_bytes()); // extra field length
+            zip.extend_from_slice(name.as_bytes());
+            zip.extend_from_slice(compressed);
+
+            central.extend_from_slice(b"PK\x01\x02");
+            let host = if unix_mode != 0 { 3 } else { 0 };
+            central.extend_from_slice(&(host << 8 | 20u16).to_le_bytes()); // version made by
+            central.extend_from_slice(&common[..]);
+            central.extend_from_slice(&(extra.len() as u16).to_le_bytes());
+            for &n in &[0u16, 0, 0] { central.extend_from_slice(&n.to_le_bytes()); } // comment length, disk, internal attributes
+            central.extend_from_slice(&(unix_mode << 16).to_le_bytes()); // external attributes
+            central.extend_from_slice(&offset.to_le_bytes());
+            central.extend_from_slice(name.as_bytes());
+            central.extend_from_slice(extra);
+        }
+        let central_offset = zip.len() as u32;
+        zip.extend_from_slice(&central[..]);
+        zip.extend_from_slice(b"PK\x05\x06");
+        for &n in &[0u16, 0, files.len() as u16, files.len() as u16] { zip.extend_from_slice(&n.to_le_bytes()); }
+        zip.extend_from_slice(&(central.len() as u32).to_le_bytes());
+        zip.extend_from_slice(&central_offset.to_le_bytes());
+        zip.extend_from_slice(&0u16.to_le_bytes()); // comment length
+        zip
+    }
+
+    /// Convert `zip` into a spanned archive (like `zip -s`) split into volumes at each of the `splits` offsets, which
+    /// account for the spanning signature prepended to the first volume.
+    fn spanned_zip(zip: &[u8], splits: &[usize]) -> Vec<Vec<u8>> {
+        let u16at = |b: &[u8], i: usize| u16::from_le_bytes([b[i], b[i+1]]) as usize;
+        let u32at = |b: &[u8], i: usize| u32::from_le_bytes([b[i], b[i+1], b[i+2], b[i+3]]) as usize;
+        let mut zip = [&b"PK\x07\x08"[..], zip].concat();
+        let locate = |offset: usize| { // (disk, offset within disk)
+            let disk = splits.iter().filter(|&&split| split <= offset).count();
+            (disk as u16, (offset - if disk == 0 { 0 } else { splits[disk-1] }) as u32)
+        };
+        let eocd = zip.len() - 22;
+        let cd_offset = u32at(&zip, eocd + 16) + 4;
+        let mut pos = cd_offset;
+        for _ in 0 .. u16at(&zip, eocd + 10) {
+            let (disk, offset) = locate(u32at(&zip, pos + 42) + 4);
+            zip[pos+34 .. pos+36].copy_from_slice(&disk.to_le_bytes());
+            zip[pos+42 .. pos+46].copy_from_slice(&offset.to_le_bytes());
+            pos += 46 + u16at(&zip, pos + 28) + u16at(&zip, pos + 30) + u16at(&zip, pos + 32);
+        }
+        let (cd_disk, cd_offset) = locate(cd_offset);
+        zip[eocd+4 .. eocd+6].copy_from_slice(&(splits.len() as u16).to_le_bytes());
+        zip[eocd+6 .. eocd+8].copy_from_slice(&cd_disk.to_le_bytes());
+        zip[eocd+16 .. eocd+20].copy_from_slice(&cd_offset.to_le_bytes());
+
+        let mut volumes = Vec::new();
+        let mut start = 0;
+        for &split in splits.iter().chain(std::iter::once(&zip.len())) {
+            volumes.push(zip[start..split].to_vec());
+            start = split;
+        }
+        volumes
+    }
+
+    /// A mostly zero-filled [ReadAt] of length `len`, for testing huge archives without huge allocations
+    #[derive(Clone)]
+    struct Sparse {
+        chunks: Arc<[(u64, Vec<u8>)]>, // (offset, nonzero data) sorted by offset
+        len:    u64,
+    }
+
+    impl ReadAt for Sparse {
+        fn read_at(&self, buf: &mut [u8], pos: u64) -> io::Result<usize> {
+            let mut n = (buf.len() as u64).min(self.len.saturating_sub(pos)) as usize;
+            for &(offset, ref chunk) in self.chunks.iter() {
+                let end = offset + chunk.len() as u64;
+                if end <= pos { continue }
+                if offset > pos { n = (offset - pos).min(n as u64) as usize; break } // zeros until the chunk
+                let src = &chunk[(pos - offset) as usize ..];
+                n = n.min(src.len());
+                buf[..n].copy_from_slice(&src[..n]);
+                return Ok(n);
+            }
+            for b in buf[..n].iter_mut() { *b = 0; }
+            Ok(n)
+        }
+    }
+
+    impl IntoCloneReadAtLen for Sparse {
+        type ReadAt = Self;
+        fn into_read_at_len(self) -> io::Result<(Self::ReadAt, u64)> { let len = self.len; Ok((self, len)) }
+    }
+
+    /// The nonzero chunks of a zip64 archive containing "big.bin" (`big` bytes of zeros ending in "big end!", with zip64
+    /// sizes in both headers) followed by "small.txt" (zip64 header offset), and the total length of the archive.
+    fn zip64_chunks(big: u64) -> (Vec<(u64, Vec<u8>)>, u64) {
+        fn le16(v: &mut Vec<u8>, n: u16) { v.extend_from_slice(&n.to_le_bytes()); }
+        fn le32(v: &mut Vec<u8>, n: u32) { v.extend_from_slice(&n.to_le_bytes()); }
+        fn le64(v: &mut Vec<u8>, n: u64) { v.extend_from_slice(&n.to_le_bytes()); }
+        let header = |v: &mut Vec<u8>, name: &str, crc32: u32, size: u32, extra: &[u8]| { // version needed .. extra field length
+            for &n in &[45u16, 0, 0, 0, 0x21] { le16(v, n); } // version needed, flags, method, dos time, dos date
+            le32(v, crc32);
+            le32(v, size);
+            le32(v, size);
+            le16(v, name.len() as u16);
+            le16(v, extra.len() as u16);
+        };
+
+        let mut big_extra = vec![1, 0, 16, 0];
+        le64(&mut big_extra, big); // uncompressed
+        le64(&mut big_extra, big); // compressed
+        let mut big_local = b"PK\x03\x04".to_vec();
+        header(&mut big_local, "big.bin", 0, 0xFFFF_FFFF, &big_extra[..]);
+        big_local.extend_from_slice(b"big.bin");
+        big_local.extend_from_slice(&big_extra[..]);
+        let big_end = big_local.len() as u64 + big;
+
+        let small_offset = big_end;
+        let mut small_local = b"PK\x03\x04".to_vec();
+        header(&mut small_local, "small.txt", crc32fast::hash(b"hello"), 5, &[]);
+        small_local.extend_from_slice(b"small.txthello");
+
+        let mut small_extra = vec![1, 0, 8, 0];
+        le64(&mut small_extra, small_offset);
+        let cd_offset = small_offset + small_local.len() as u64;
+        let mut tail = Vec::new();
+        for &(name, crc32, size, offset, extra) in &[("big.bin", 0, 0xFFFF_FFFF, 0, &big_extra), ("small.txt", crc32fast::hash(b"hello"), 5, 0xFFFF_FFFF, &small_extra)] {
+            tail.extend_from_slice(b"PK\x01\x02");
+            le16(&mut tail, 45); // version made by
+            header(&mut tail, name, crc32, size, &extra[..]);
+            for &n in &[0u16, 0, 0] { le16(&mut tail, n); } // comment length, disk, internal attributes
+            le32(&mut tail, 0); // external attributes
+            le32(&mut tail, offset);
+            tail.extend_from_slice(name.as_bytes());
+            tail.extend_from_slice(&extra[..]);
+        }
+        let cd_size = tail.len() as u64;
+        let zip64_eocd = cd_offset + cd_size;
+        tail.extend_from_slice(b"PK\x06\x06");
+        le64(&mut tail, 44); // size of the remaining record
+        for &n in &[45u16, 45] { le16(&mut tail, n); } // version made by, version needed
+        for &n in &[0u32, 0] { le32(&mut tail, n); } // disk, central directory disk
+        for &n in &[2u64, 2, cd_size, cd_offset] { le64(&mut tail, n); }
+        tail.extend_from_slice(b"PK\x06\x07");
+        le32(&mut tail, 0); // disk
+        le64(&mut tail, zip64_eocd);
+        le32(&mut tail, 1); // total disks
+        tail.extend_from_slice(b"PK\x05\x06");
+        for &n in &[0u16, 0, 0xFFFF, 0xFFFF] { le16(&mut tail, n); }
+        for &n in &[0xFFFF_FFFFu32, 0xFFFF_FFFF] { le32(&mut tail, n); } // central directory size, offset
+        le16(&mut tail, 0); // comment length
+
+        let len = cd_offset + tail.len() as u64;
+        let chunks = vec![(0, big_local), (big_end - 8, b"big end!".to_vec()), (small_offset, small_local), (cd_offset, tail)];
+        (chunks, len)
     }
 
     fn with_zip<'a>(src: &str, files: impl Iterator<Item = &'a str>, dirs: impl Iterator<Item = &'a str>, zip: &impl FileSystem) {
@@ -195,10 +1252,10 @@ impl<IO: Clone + ReadAt + Send + Sync + 'static> FileSystem for ZipReadOnly<IO>
                 format!("/{}", file),
             ] {
                 eprintln!("{}", good);
-                zip.read_dir(&good).err().unwrap();
-                zip.open_file(&good).unwrap();
-                zip.metadata(&good).unwrap();
-                assert_eq!(zip.exists(&good), true);
+                zip.read_dir(good).err().unwrap();
+                zip.open_file(good).unwrap().read_to_end(&mut Vec::new()).unwrap();
+                zip.metadata(good).unwrap();
+                assert_eq!(zip.exists(good), true);
             }
 
             for bad in &[
@@ -211,10 +1268,10 @@ impl<IO: Clone + ReadAt + Send + Sync + 'static> FileSystem for ZipReadOnly<IO>
                 format!("./{}/", file),
             ] {
                 eprintln!("{}", bad);
-                zip.read_dir(&bad).err().unwrap();
-                zip.open_file(&bad).err().unwrap();
-                zip.metadata(&bad).err().unwrap();
-                assert_eq!(zip.exists(&bad), false);
+                zip.read_dir(bad).err().unwrap();
+                zip.open_file(bad).err().unwrap();
+                zip.metadata(bad).err().unwrap();
+                assert_eq!(zip.exists(bad), false);
             }
         }
 
@@ -226,10 +1283,10 @@ impl<IO: Clone + ReadAt + Send + Sync + 'static> FileSystem for ZipReadOnly<IO>
                 format!("/{}/", dir),
             ] {
                 eprintln!("{}", good);
-                let _ = zip.read_dir(&good).unwrap().collect::<Vec<String>>();
-                zip.open_file(&good).err().unwrap();
-                zip.metadata(&good).unwrap();
-                assert_eq!(zip.exists(&good), true);
+                let _ = zip.read_dir(good).unwrap().collect::<Vec<String>>();
+                zip.open_file(good).err().unwrap();
+                zip.metadata(good).unwrap();
+                assert_eq!(zip.exists(good), true);
             }
 
             for bad in &[
@@ -243,10 +1300,10 @@ impl<IO: Clone + ReadAt + Send + Sync + 'static> FileSystem for ZipReadOnly<IO>
                 format!("\\{}", dir),
             ] {
                 eprintln!("{}", bad);
-                zip.read_dir(&bad).err().unwrap();
-                zip.open_file(&bad).err().unwrap();
-                zip.metadata(&bad).err().unwrap();
-                assert_eq!(zip.exists(&bad), false);
+                zip.read_dir(bad).err().unwrap();
+                zip.open_file(bad).err().unwrap();
+                zip.metadata(bad).err().unwrap();
+                assert_eq!(zip.exists(bad), false);
             }
         }
     }
@@ -0,0 +1,110 @@
+use super::AbsSeekPos;
+
+use std::io::{self, Read, Seek, SeekFrom};
+
+
+
+const HEADER_SIZE : u64 = 12;
+
+/// A seekable traditional PKWARE ("ZipCrypto") decryptor.
+///
+/// ZipCrypto is a stream cipher whose state depends on every previous plaintext byte, so seeking backwards restarts
+/// decryption from just after the 12-byte encryption header.  Seeking forwards decrypts and discards.
+pub(crate) struct ZipCrypto<R> {
+    inner:      R,
+    initial:    ZipCryptoKeys,  // keys just after the encryption header
+    keys:       ZipCryptoKeys,
+    length:     u64,            // decrypted length (excludes the encryption header)
+    pos:        u64,            // decrypted bytes read from inner so far
+    target:     u64,            // logical seek position
+}
+
+impl<R: Read + Seek> ZipCrypto<R> {
+    /// Decrypt the encryption header at the start of `inner`, and verify its last byte matches `check`.
+    /// An incorrect password results in a [PermissionDenied](io::ErrorKind::PermissionDenied) error.
+    pub fn new(mut inner: R, password: &[u8], check: u8, length: u64) -> io::Result<Self> {
+        let mut keys = ZipCryptoKeys::new(password);
+        let mut header = [0u8; HEADER_SIZE as usize];
+        inner.seek(SeekFrom::Start(0))?;
+        inner.read_exact(&mut header[..])?;
+        keys.decrypt(&mut header[..]);
+        if header[11] != check { return Err(io::Error::new(io::ErrorKind::PermissionDenied, "invalid password for encrypted file entry")) }
+        Ok(Self { inner, initial: keys.clone(), keys, length, pos: 0, target: 0 })
+    }
+
+    fn reposition(&mut self) -> io::Result<()> {
+        if self.target < self.pos {
+            self.inner.seek(SeekFrom::Start(HEADER_SIZE))?;
+            self.keys   = self.initial.clone();
+            self.pos    = 0;
+        }
+
+        let mut scratch = [0u8; 8 * 1024];
+        while self.pos < self.target {
+            let n = ((self.target - self.pos).min(scratch.len() as u64)) as usize;
+            let read = self.inner.read(&mut scratch[..n])?;
+            if read == 0 { break } // seeked past EOF
+            self.keys.decrypt(&mut scratch[..read]);
+            self.pos += read as u64;
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek> Read for ZipCrypto<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.target != self.pos { self.reposition()?; }
+        if self.pos < self.target { return Ok(0) }
+        let read = self.inner.read(buf)?;
+        self.keys.decrypt(&mut buf[..read]);
+        self.pos += read as u64;
+        self.target = self.pos;
+        Ok(read)
+    }
+}
+
+impl<R: Read + Seek> Seek for ZipCrypto<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.target = AbsSeekPos(self.target).offset_bounded(pos, self.length)?.0;
+        Ok(self.target)
+    }
+}
+
+
+
+/// The 96-bit internal state of the traditional PKWARE cipher
+#[derive(Clone)]
+pub(crate) struct ZipCryptoKeys([u32; 3]);
+
+impl ZipCryptoKeys {
+    pub fn new(password: &[u8]) -> Self {
+        let mut keys = ZipCryptoKeys([0x1234_5678, 0x2345_6789, 0x3456_7890]);
+        for &b in password { keys.update(b); }
+        keys
+    }
+
+    /// The next byte of keystream, to be XORed with the next byte of plaintext / ciphertext
+    pub fn stream_byte(&self) -> u8 {
+        let temp = (self.0[2] | 2) & 0xFFFF;
+        ((temp * (temp ^ 1)) >> 8) as u8
+    }
+
+    /// Advance the cipher state past the plaintext byte `plain`
+    pub fn update(&mut self, plain: u8) {
+        self.0[0] = crc32_byte(self.0[0], plain);
+        self.0[1] = self.0[1].wrapping_add(self.0[0] & 0xFF).wrapping_mul(134_775_813).wrapping_add(1);
+        self.0[2] = crc32_byte(self.0[2], (self.0[1] >> 24) as u8);
+    }
+
+    fn decrypt(&mut self, buf: &mut [u8]) {
+        for b in buf.iter_mut() {
+            *b ^= self.stream_byte();
+            self.update(*b);
+        }
+    }
+}
+
+/// Single byte step of a (non-inverted) CRC32
+fn crc32_byte(crc: u32, b: u8) -> u32 {
+    (0..8).fold(crc ^ b as u32, |crc, _| if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 })
+}
@@ -0,0 +1,173 @@
+use super::{extra_field, ExtraFields};
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+
+
+/// Metadata about a file or directory within a [ZipReadOnly](crate::ZipReadOnly), as returned by
+/// [ZipReadOnly::zip_metadata](crate::ZipReadOnly::zip_metadata).
+#[derive(Clone, Debug)]
+pub struct ZipMetadata {
+    pub(crate) is_dir:      bool,
+    pub(crate) len:         u64,
+    pub(crate) modified:    Option<SystemTime>,
+    pub(crate) version_made_by:     u16,
+    pub(crate) external_attributes: u32,
+    pub(crate) comment:     Vec<u8>,
+    pub(crate) raw_name:    Option<String>,
+    pub(crate) central_extra:   Vec<u8>,
+    pub(crate) local_extra:     Vec<u8>,
+}
+
+impl ZipMetadata {
+    /// `true` if this is a directory (explicit or implied by the paths of other entries)
+    pub fn is_dir(&self) -> bool { self.is_dir }
+
+    /// `true` if this is a file (and not a symlink)
+    pub fn is_file(&self) -> bool { !self.is_dir && !self.is_symlink() }
+
+    /// `true` if this is a symlink: an entry created on a Unix host (e.g. by `zip -y`) with a file mode of `S_IFLNK`,
+    /// whose contents are the target path.  See [ZipReadOnly::read_link](crate::ZipReadOnly::read_link).
+    pub fn is_symlink(&self) -> bool { is_symlink(self.version_made_by, self.external_attributes) }
+
+    /// The uncompressed size of the file in bytes, or 0 for directories
+    #[allow(clippy::len_without_is_empty)] // like std::fs::Metadata::len
+    pub fn len(&self) -> u64 { self.len }
+
+    /// The last modification time of the file, if known.
+    ///
+    /// NTFS (0x000a) and Info-ZIP extended timestamp (0x5455) extra fields are preferred when present.  Otherwise, this
+    /// falls back on the MS-DOS timestamp, which has 2 second precision and no timezone (it's assumed to be UTC.)
+    /// Always [None] for directories.
+    pub fn modified(&self) -> Option<SystemTime> { self.modified }
+
+    /// The host system of the "version made by" field, which determines how to interpret [external_attributes].
+    /// Common values include 0 (MS-DOS / FAT), 3 (Unix), 10 (Windows NTFS), and 19 (OS X).  Always 0 for directories.
+    ///
+    /// [external_attributes]:  Self::external_attributes
+    pub fn host_system(&self) -> u8 { (self.version_made_by >> 8) as u8 }
+
+    /// The raw "external file attributes" of the entry.  The low byte usually contains MS-DOS attributes, and for Unix
+    /// hosts the high 16 bits contain the file mode.  Always 0 for directories.
+    pub fn external_attributes(&self) -> u32 { self.external_attributes }
+
+    /// The Unix file mode (type and permission bits) if the entry was created on a Unix host, e.g. `0o100755` for an
+    /// executable file.
+    ///
+    /// ```rust
+    /// # use vfs_zip::*;
+    /// # fn is_executable(metadata: &ZipMetadata) -> bool {
+    /// metadata.unix_mode().map_or(false, |mode| mode & 0o111 != 0)
+    /// # }
+    /// ```
+    pub fn unix_mode(&self) -> Option<u32> { unix_mode(self.version_made_by, self.external_attributes) }
+
+    /// The entry's comment from the central directory, or an empty slice if there isn't one.
+    /// Like [ZipReadOnly::comment](crate::ZipReadOnly::comment), this has no specified encoding.
+    pub fn comment(&self) -> &[u8] { &self.comment[..] }
+
+    /// The entry's name as stored in the archive, if vfs-zip altered it while indexing (e.g. converting backslashes via
+    /// [ZipReadOnlyOptions::backslash_separators](crate::ZipReadOnlyOptions::backslash_separators)), or [None] otherwise.
+    pub fn raw_name(&self) -> Option<&str> { self.raw_name.as_ref().map(|n| &n[..]) }
+
+    /// The raw `(header_id, data)` extra fields from the entry's central directory header, including any that vfs-zip
+    /// interprets itself (Zip64, timestamps, AES, ...)
+    ///
+    /// Only available if the archive was opened with [ZipReadOnlyOptions::keep_extra_fields](crate::ZipReadOnlyOptions::keep_extra_fields),
+    /// otherwise empty.  Always empty for directories.
+    pub fn extra_fields(&self) -> ExtraFields<'_> { ExtraFields::new(&self.central_extra[..]) }
+
+    /// The raw `(header_id, data)` extra fields from the entry's local file header, which may differ from the central
+    /// directory's (e.g. alignment padding, or timestamps with access/creation times.)
+    ///
+    /// Only available if the archive was opened with [ZipReadOnlyOptions::keep_extra_fields](crate::ZipReadOnlyOptions::keep_extra_fields),
+    /// otherwise empty.  Always empty for directories.
+    pub fn local_extra_fields(&self) -> ExtraFields<'_> { ExtraFields::new(&self.local_extra[..]) }
+
+    /// The MS-DOS read-only attribute (`0x01`)
+    pub fn is_readonly(&self) -> bool { self.external_attributes & 0x01 != 0 }
+
+    /// The MS-DOS hidden attribute (`0x02`)
+    pub fn is_hidden(&self) -> bool { self.external_attributes & 0x02 != 0 }
+
+    /// The MS-DOS system attribute (`0x04`)
+    pub fn is_system(&self) -> bool { self.external_attributes & 0x04 != 0 }
+}
+
+pub(crate) fn unix_mode(version_made_by: u16, external_attributes: u32) -> Option<u32> {
+    let mode = external_attributes >> 16;
+    match version_made_by >> 8 {
+        3 | 19 if mode != 0 => Some(mode), // Unix, OS X
+        _ => None,
+    }
+}
+
+pub(crate) fn is_symlink(version_made_by: u16, external_attributes: u32) -> bool {
+    const S_IFMT    : u32 = 0o170000;
+    const S_IFLNK   : u32 = 0o120000;
+    unix_mode(version_made_by, external_attributes).map_or(false, |mode| mode & S_IFMT == S_IFLNK)
+}
+
+/// Determine the modification time of an entry from its MS-DOS timestamp and central directory `extra` fields
+pub(crate) fn modified_time(dos_date: u16, dos_time: u16, extra: &[u8]) -> Option<SystemTime> {
+    ntfs_modified(extra).or_else(|| extended_timestamp_modified(extra)).or_else(|| dos_modified(dos_date, dos_time))
+}
+
+/// NTFS extra field: u32 reserved, then attributes of (u16 tag, u16 size, data).
+/// Tag 1 is u64 mtime, atime, ctime in 100ns intervals since 1601-01-01.
+fn ntfs_modified(extra: &[u8]) -> Option<SystemTime> {
+    let mut attributes = extra_field(extra, 0x000a)?.get(4..)?;
+    while attributes.len() >= 4 {
+        let tag     = u16::from_le_bytes([attributes[0], attributes[1]]);
+        let size    = u16::from_le_bytes([attributes[2], attributes[3]]) as usize;
+        let data    = attributes[4..].get(..size)?;
+        if tag == 1 && size >= 8 {
+            let mut mtime = [0u8; 8];
+            mtime.copy_from_slice(&data[..8]);
+            let ticks = u64::from_le_bytes(mtime);
+            const UNIX_EPOCH_TICKS : u64 = 116_444_736_000_000_000;
+            let since_1601 = Duration::new(ticks / 10_000_000, (ticks % 10_000_000) as u32 * 100);
+            let epoch_1601 = UNIX_EPOCH.checked_sub(Duration::from_secs(UNIX_EPOCH_TICKS / 10_000_000))?;
+            return epoch_1601.checked_add(since_1601);
+        }
+        attributes = &attributes[4+size..];
+    }
+    None
+}
+
+/// Info-ZIP extended timestamp extra field: u8 flags, then i32 seconds since the unix epoch for each flag set.
+/// Bit 0 is mtime, which comes first.  (The central directory copy only ever contains mtime.)
+fn extended_timestamp_modified(extra: &[u8]) -> Option<SystemTime> {
+    let field = extra_field(extra, 0x5455)?;
+    if field.first()? & 1 == 0 { return None }
+    let mtime = field.get(1..5)?;
+    let secs = i32::from_le_bytes([mtime[0], mtime[1], mtime[2], mtime[3]]) as i64;
+    if secs >= 0 {
+        UNIX_EPOCH.checked_add(Duration::from_secs(secs as u64))
+    } else {
+        UNIX_EPOCH.checked_sub(Duration::from_secs(secs.wrapping_neg() as u64))
+    }
+}
+
+/// MS-DOS date: 7 bit year since 1980, 4 bit month, 5 bit day.  MS-DOS time: 5 bit hour, 6 bit minute, 5 bit second / 2.
+/// These are conventionally the writer's local time, but the zip doesn't record that timezone (and std can't tell us
+/// ours), so this treats them as UTC.  Only used when there's no NTFS or extended timestamp extra field.
+fn dos_modified(date: u16, time: u16) -> Option<SystemTime> {
+    let (y, m, d) = (1980 + (date >> 9) as i64, (date >> 5 & 0xF) as u32, (date & 0x1F) as u32);
+    let (hour, minute, second) = ((time >> 11) as i64, (time >> 5 & 0x3F) as i64, (time & 0x1F) as i64 * 2);
+    if m < 1 || m > 12 || d < 1 || d > 31 || hour > 23 || minute > 59 || second > 59 { return None }
+    let days = days_from_civil(y, m, d);
+    let secs = days * 86400 + hour * 3600 + minute * 60 + second;
+    UNIX_EPOCH.checked_add(Duration::from_secs(secs as u64)) // DOS dates start at 1980, so secs >= 0
+}
+
+/// (year, month, day) -> days since 1970-01-01, per <http://howardhinnant.github.io/date_algorithms.html#days_from_civil>
+fn days_from_civil(y: i64, m: u32, d: u32) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = if y >= 0 { y } else { y - 399 } / 400;
+    let yoe = (y - era * 400) as u64;
+    let mp = if m > 2 { m - 3 } else { m + 9 } as u64;
+    let doy = (153*mp + 2)/5 + d as u64 - 1;
+    let doe = yoe * 365 + yoe/4 - yoe/100 + doy;
+    era * 146097 + doe as i64 - 719468
+}
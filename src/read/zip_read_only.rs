@@ -1,28 +1,101 @@
 use crate::{Error, Result};
-use super::{ReadAtCursor, ReadAtRef, IntoCloneReadAtLen};
+use super::{decode_cp437, extra_field, fold_case, modified_time, read_central_directory, read_local_header, to_nfc, CentralRecord, FilenameDecoder, IntoCloneReadAtLen, LazyIndex, ZipDuplicatePolicy, ZipMetadata, ZipReadOnlyOptions, ZipWarning, ZipWarningKind};
+#[cfg(feature = "zip-bzip2")] use super::Bzip2Index;
+#[cfg(any(feature = "zip-deflate", feature = "zip-deflate64"))] use super::InflateIndex;
 
 use read_write_at::ReadAt;
 
+use std::borrow::Cow;
 use std::collections::*;
 use std::convert::*;
 use std::fmt::{self, Debug, Formatter};
 use std::path::*;
+#[cfg(any(feature = "zip-bzip2", feature = "zip-deflate", feature = "zip-deflate64"))] use std::sync::{Arc, Mutex, Weak};
+use std::time::SystemTime;
 
 
 
 /// A read-only zip archive filesystem
 pub struct ZipReadOnly<IO: ReadAt> {
-    pub(super) io:     IO,
-    pub(super) files:  BTreeMap<String, FileEntry>, // abs path -> ...
-    pub(super) dirs:   BTreeMap<String, BTreeSet<String>>, // abs path -> [relative file/dir names]
+    pub(super) io:      IO,
+    pub(super) len:     u64, // archive length, for validating index caches
+    pub(super) options: ZipReadOnlyOptions,
+    pub(super) files:   BTreeMap<String, FileEntry>, // abs path -> ...
+    pub(super) dirs:    BTreeMap<String, BTreeSet<String>>, // abs path -> [relative file/dir names]
+    pub(super) comment: Vec<u8>,
+    pub(super) dir_entries:     BTreeMap<String, DirEntry>, // abs path -> ..., only for explicit directory entries with comments etc.
+    pub(super) folded:  BTreeMap<String, String>, // case folded abs path -> abs path, only if ZipReadOnlyOptions::case_insensitive
+    pub(super) warnings:        Vec<ZipWarning>,
+    pub(super) lazy:            Option<LazyIndex>, // replaces files, dirs, dir_entries if ZipReadOnlyOptions::lazy_index
+    #[cfg(any(feature = "zip-deflate", feature = "zip-deflate64"))]
+    pub(super) inflate_indices: Mutex<BTreeMap<u64, Weak<Mutex<InflateIndex>>>>, // header_offset -> deflate checkpoints shared by open files
+    #[cfg(feature = "zip-bzip2")]
+    pub(super) bzip2_indices:   Mutex<BTreeMap<u64, Weak<Mutex<Bzip2Index>>>>,   // header_offset -> bzip2 block offsets shared by open files
 }
 
+#[derive(Clone)]
 pub(super) struct FileEntry {
-    pub header_offset:  u64,
-    pub header_size:    u64,
+    pub header_offset:  u64, // the local header's size is only read when opening the entry
     pub compressed:     u64,
     pub uncompressed:   u64,
-    pub compression:    zip::CompressionMethod,
+    pub compression:    zip::CompressionMethod, // the real compression method, even for AES encrypted entries
+    pub crc32:          u32,
+    pub aes:            Option<AesEntry>,
+    pub modified:       Option<SystemTime>,
+    pub version_made_by:        u16,
+    pub external_attributes:    u32,
+    pub comment:                Vec<u8>,
+    pub raw_name:               Option<String>, // only if different from the abs path (e.g. backslashes converted)
+    pub central_extra:          Vec<u8>, // only kept if ZipReadOnlyOptions::keep_extra_fields
+    pub local_extra:            Vec<u8>, // only kept if ZipReadOnlyOptions::keep_extra_fields
+}
+
+impl FileEntry {
+    /// Build an entry from its central directory `record` (without `raw_name` or `local_extra`), or [None] if it's AES
+    /// encrypted without a valid 0x9901 extra field.
+    pub fn from_record(record: &CentralRecord, keep_extra_fields: bool) -> Option<Self> {
+        #[allow(deprecated)] let mut compression = zip::CompressionMethod::from_u16(record.compression);
+        let mut aes = None;
+        #[allow(deprecated)] let aes_encrypted = compression == zip::CompressionMethod::Unsupported(99); // WinZip AES
+        if aes_encrypted {
+            let f = extra_field(record.extra, 0x9901).filter(|f| f.len() >= 7 && &f[2..4] == b"AE")?;
+            aes = Some(AesEntry { version: u16::from_le_bytes([f[0], f[1]]), strength: f[4] });
+            #[allow(deprecated)] { compression = zip::CompressionMethod::from_u16(u16::from_le_bytes([f[5], f[6]])); }
+        }
+
+        Some(FileEntry {
+            header_offset:  record.header_offset,
+            compressed:     record.compressed,
+            uncompressed:   record.uncompressed,
+            compression,
+            crc32:          record.crc32,
+            aes,
+            modified:       modified_time(record.dos_date, record.dos_time, record.extra),
+            version_made_by:        record.version_made_by,
+            external_attributes:    record.external_attributes,
+            comment:                record.comment.to_vec(),
+            raw_name:               None,
+            central_extra:          if keep_extra_fields { record.extra.to_vec() } else { Vec::new() },
+            local_extra:            Vec::new(),
+        })
+    }
+
+    pub fn is_symlink(&self) -> bool { super::is_symlink(self.version_made_by, self.external_attributes) }
+}
+
+/// Details of an explicit directory entry not implied by `dirs`
+#[derive(Clone, Default)]
+pub(super) struct DirEntry {
+    pub comment:    Vec<u8>,
+    pub raw_name:   Option<String>,
+}
+
+/// WinZip AES encryption details, from the 0x9901 extra field
+#[derive(Clone, Copy)]
+pub(super) struct AesEntry {
+    pub version:        u16, // 1 = AE-1, 2 = AE-2 (CRC omitted)
+    #[cfg_attr(not(feature = "zip-aes"), allow(dead_code))]
+    pub strength:       u8,  // 1 = AES-128, 2 = AES-192, 3 = AES-256
 }
 
 impl<IO: ReadAt> Debug for ZipReadOnly<IO> {
@@ -31,47 +104,260 @@ impl<IO: ReadAt> Debug for ZipReadOnly<IO> {
     }
 }
 
+impl<IO: ReadAt> ZipReadOnly<IO> {
+    /// Get metadata about the file or directory at `path`, including information not exposed through [vfs] traits
+    /// such as modification times.
+    ///
+    /// [vfs]:  https://docs.rs/vfs/
+    pub fn zip_metadata(&self, path: &str) -> Result<ZipMetadata> {
+        let (path, dir) = normalize_path_dir(path).ok_or_else(|| Error::io(std::io::Error::new(std::io::ErrorKind::InvalidInput, "invalid path")))?;
+        let path = self.canonical(path);
+        let path = &*path;
+        if let Some(e) = self.file(path).filter(|_| !dir) {
+            let e = e.into_owned();
+            let local_extra = if self.lazy.is_some() && self.options.keep_extra_fields { local_extra_field(&self.io, e.header_offset).map_err(Error::io)? } else { e.local_extra };
+            Ok(ZipMetadata { is_dir: false, len: e.uncompressed, modified: e.modified, version_made_by: e.version_made_by, external_attributes: e.external_attributes, comment: e.comment, raw_name: e.raw_name, central_extra: e.central_extra, local_extra })
+        } else if self.is_dir(path) {
+            let (comment, raw_name) = self.dir_entry(path).map_or((Vec::new(), None), |d| { let d = d.into_owned(); (d.comment, d.raw_name) });
+            Ok(ZipMetadata { is_dir: true, len: 0, modified: None, version_made_by: 0, external_attributes: 0, comment, raw_name, central_extra: Vec::new(), local_extra: Vec::new() })
+        } else {
+            Err(Error::not_found())
+        }
+    }
+
+    /// The archive comment (from the end of central directory record), or an empty slice if there isn't one.
+    ///
+    /// Zip comments have no specified encoding: they're usually ASCII or UTF-8, but older tools may use CP437.
+    pub fn comment(&self) -> &[u8] { &self.comment[..] }
+
+    /// Entries that were skipped or deduplicated while indexing the archive, in central directory order.
+    /// Entries with errors are only skipped (instead of failing to open the archive) with
+    /// [ZipReadOnlyOptions::ignore_file_errors], as in [new_relaxed](Self::new_relaxed).
+    pub fn warnings(&self) -> &[ZipWarning] { &self.warnings[..] }
+
+    /// Map a normalized `path` to the spelling used by `files` / `dirs`, if [ZipReadOnlyOptions::case_insensitive] is enabled.
+    /// Also NFC normalizes `path` if [ZipReadOnlyOptions::unicode_nfc] is enabled.
+    pub(super) fn canonical<'s>(&self, path: &'s str) -> Cow<'s, str> {
+        let path = if self.options.unicode_nfc { to_nfc(path) } else { Cow::Borrowed(path) };
+        if !self.options.case_insensitive || self.files.contains_key(&*path) || self.dirs.contains_key(&*path) { return path }
+        match self.folded.get(&fold_case(&path)) {
+            Some(canonical) => Cow::Owned(canonical.clone()),
+            None            => path,
+        }
+    }
+
+    /// The file entry at (canonical) `path`
+    pub(super) fn file(&self, path: &str) -> Option<Cow<'_, FileEntry>> {
+        match self.lazy.as_ref() {
+            Some(lazy)  => lazy.file(path).map(Cow::Owned),
+            None        => self.files.get(path).map(Cow::Borrowed),
+        }
+    }
+
+    /// Is there a directory at (canonical) `path`?
+    pub(super) fn is_dir(&self, path: &str) -> bool {
+        match self.lazy.as_ref() {
+            Some(lazy)  => lazy.is_dir(path),
+            None        => self.dirs.contains_key(path),
+        }
+    }
+
+    /// The names of the files and directories directly within the directory at (canonical) `path`, sorted
+    pub(super) fn dir_children(&self, path: &str) -> Option<Vec<String>> {
+        match self.lazy.as_ref() {
+            Some(lazy)  => lazy.dir_children(path).map(|children| children.into_iter().collect()),
+            None        => self.dirs.get(path).map(|children| children.iter().cloned().collect()),
+        }
+    }
+
+    /// Details of the explicit directory entry at (canonical) `path`, if there is one
+    fn dir_entry(&self, path: &str) -> Option<Cow<'_, DirEntry>> {
+        match self.lazy.as_ref() {
+            Some(lazy)  => lazy.dir_record(path).map(|record| Cow::Owned(DirEntry { comment: record.comment.to_vec(), raw_name: None })),
+            None        => self.dir_entries.get(path).map(Cow::Borrowed),
+        }
+    }
+
+    /// Respell the archive path `abs` (and its parent directories) to match previously indexed entries that differ only by case.
+    /// Fails if the entry differs only by case from a previously indexed entry of a different type (file vs directory.)
+    fn case_canonical(&self, abs: &str, is_dir: bool) -> Result<String> {
+        let mut canonical = String::with_capacity(abs.len());
+        let leaf = abs.split('/').count() - 1;
+        for (i, component) in abs.split('/').enumerate() {
+            if i > 0 { canonical.push('/'); }
+            canonical.push_str(component);
+            if let Some(existing) = self.folded.get(&fold_case(&canonical)) {
+                let same_type = if is_dir { self.dirs.contains_key(existing) } else { self.files.contains_key(existing) };
+                if i == leaf && *existing != canonical && !same_type {
+                    return Err(Error::unsupported("vfs-zip found entries whose paths differ only by case (see ZipReadOnlyOptions::case_insensitive)"));
+                }
+                canonical.clone_from(existing);
+            }
+        }
+        Ok(canonical)
+    }
+
+    /// Get (or create) the deflate checkpoints shared by every open of the file entry at `header_offset`.
+    /// Checkpoints are dropped once every open of their entry is closed, so they don't accumulate for every entry ever read.
+    #[cfg(any(feature = "zip-deflate", feature = "zip-deflate64"))]
+    pub(super) fn inflate_index(&self, header_offset: u64) -> Arc<Mutex<InflateIndex>> {
+        let mut indices = self.inflate_indices.lock().unwrap();
+        if let Some(index) = indices.get(&header_offset).and_then(Weak::upgrade) { return index }
+
+        let closed = indices.iter().filter(|(_, index)| index.upgrade().is_none()).map(|(&offset, _)| offset).collect::<Vec<_>>();
+        for offset in closed { indices.remove(&offset); }
+        let index = Arc::new(Mutex::new(InflateIndex::new(self.options.deflate_checkpoint_interval)));
+        indices.insert(header_offset, Arc::downgrade(&index));
+        index
+    }
+
+    /// Get (or create) the bzip2 block offsets shared by every open of the file entry at `header_offset`.
+    /// Like [inflate_index](Self::inflate_index), these are dropped once every open of their entry is closed.
+    #[cfg(feature = "zip-bzip2")]
+    pub(super) fn bzip2_index(&self, header_offset: u64) -> Arc<Mutex<Bzip2Index>> {
+        let mut indices = self.bzip2_indices.lock().unwrap();
+        if let Some(index) = indices.get(&header_offset).and_then(Weak::upgrade) { return index }
+
+        let closed = indices.iter().filter(|(_, index)| index.upgrade().is_none()).map(|(&offset, _)| offset).collect::<Vec<_>>();
+        for offset in closed { indices.remove(&offset); }
+        let index = Arc::new(Mutex::new(Bzip2Index::default()));
+        indices.insert(header_offset, Arc::downgrade(&index));
+        index
+    }
+}
+
 impl<IO: Clone + ReadAt> ZipReadOnly<IO> {
     /// Create a new read-only zip filesystem.
     /// Any archive errors (including unsupported paths) will result in errors.
-    pub fn new_strict(cral: impl IntoCloneReadAtLen<ReadAt = IO>) -> Result<Self> { Self::new_impl(cral, false) }
+    pub fn new_strict(cral: impl IntoCloneReadAtLen<ReadAt = IO>) -> Result<Self> { Self::new_with_options(cral, ZipReadOnlyOptions::strict()) }
 
     /// Create a new read-only zip filesystem.
     /// Some archive errors (such as unsupported paths) will be ignored.
-    pub fn new_relaxed(cral: impl IntoCloneReadAtLen<ReadAt = IO>) -> Result<Self> { Self::new_impl(cral, true) }
+    pub fn new_relaxed(cral: impl IntoCloneReadAtLen<ReadAt = IO>) -> Result<Self> { Self::new_with_options(cral, ZipReadOnlyOptions::relaxed()) }
 
-    fn new_impl(cral: impl IntoCloneReadAtLen<ReadAt = IO>, ignore_file_errors: bool) -> Result<Self> {
+    /// Create a new read-only zip filesystem.
+    /// Error handling, memory limits, etc. are controlled by `options`.
+    pub fn new_with_options(cral: impl IntoCloneReadAtLen<ReadAt = IO>, options: ZipReadOnlyOptions) -> Result<Self> {
+        let volumes = cral.volume_offsets();
         let (cra, len) = cral.into_read_at_len().map_err(Error::io)?;
+        Self::from_read_at_len(cra, len, &volumes[..], options)
+    }
+
+    /// Index the archive `cra` of length `len` (and with `volumes` per [IntoCloneReadAtLen::volume_offsets]) from its central directory
+    pub(super) fn from_read_at_len(cra: IO, len: u64, volumes: &[u64], options: ZipReadOnlyOptions) -> Result<Self> {
+        let ignore_file_errors = options.ignore_file_errors;
+        let keep_extra_fields = options.keep_extra_fields;
+        let case_insensitive = options.case_insensitive;
+        let backslash_separators = options.backslash_separators;
+        let duplicates = options.duplicates;
+        let filename_decoder = options.filename_decoder.clone();
+        let unicode_nfc = options.unicode_nfc;
         let mut zro = Self {
             io: cra,
+            len,
+            options,
             files:  Default::default(),
             dirs:   Default::default(),
+            comment:        Vec::new(),
+            dir_entries:    Default::default(),
+            folded:         Default::default(),
+            warnings:       Vec::new(),
+            lazy:           None,
+            #[cfg(any(feature = "zip-deflate", feature = "zip-deflate64"))]
+            inflate_indices: Default::default(),
+            #[cfg(feature = "zip-bzip2")]
+            bzip2_indices:   Default::default(),
         };
 
-        let mut archive = zip::read::ZipArchive::new(ReadAtCursor::new(ReadAtRef(&zro.io), len)).map_err(Error)?;
+        let mut central_directory = read_central_directory(&zro.io, len, volumes).map_err(Error::io)?;
+        zro.comment = std::mem::replace(&mut central_directory.comment, Vec::new());
+        if zro.options.lazy_index {
+            zro.lazy = Some(LazyIndex::new(central_directory, &zro.options, &mut zro.warnings)?);
+            return Ok(zro);
+        }
+        let records = central_directory.records().collect::<std::io::Result<Vec<_>>>().map_err(Error::io)?;
         zro.dirs.insert(String::new(), Default::default()); // always have a root directory
 
-        'files: for i in 0..archive.len() {
-            let entry = archive.by_index(i);
-            if ignore_file_errors && entry.is_err() { continue; }
-            let entry = entry.map_err(Error)?;
-            let abs = entry.name();
-            if abs.contains('\\')           { if ignore_file_errors { continue } return Err(Error::unsupported("vfs-zip doesn't support zip archives containing backslashes in paths")); }
-            if abs.contains("//")           { if ignore_file_errors { continue } return Err(Error::unsupported("vfs-zip doesn't support zip archives containing 0-length directory names")); }
+        // Skip the current entry with a warning if ignoring file errors, otherwise fail with `err`
+        macro_rules! skip { ($i:expr, $name:expr, $kind:expr, $err:expr) => {{
+            if !ignore_file_errors { return Err($err) }
+            zro.warnings.push(ZipWarning { index: $i, name: $name.into(), kind: $kind });
+            continue; // only used directly within 'files
+        }}}
+
+        'files: for (i, (_, record)) in records.iter().enumerate() {
+            let name = decode_name(record, filename_decoder.as_ref());
+            let name = &*name;
+            let is_dir = name.ends_with('/') || name.ends_with('\\');
+            let mut abs = name;
+            let converted_abs;
+            let mut raw_name = None;
+            if backslash_separators && abs.contains('\\') {
+                converted_abs = abs.replace('\\', "/");
+                raw_name = Some(abs.to_owned());
+                abs = &converted_abs[..];
+            }
+            if let Some((kind, err)) = invalid_name(abs) { skip!(i, name, kind, err); }
+            let nfc_abs;
+            if unicode_nfc {
+                if let Cow::Owned(nfc) = to_nfc(abs) {
+                    nfc_abs = nfc;
+                    raw_name = Some(name.to_owned());
+                    abs = &nfc_abs[..];
+                }
+            }
             let mut abs = abs.trim_end_matches('/');
-            if Path::new(abs).is_absolute() { if ignore_file_errors { continue } return Err(Error::unsupported("vfs-zip doesn't support zip archives containing absolute paths")); }
-
-            if entry.is_file() {
-                let entry = FileEntry {
-                    header_offset:  entry.header_start(),
-                    header_size:    entry.data_start() - entry.header_start(),
-                    compressed:     entry.compressed_size(),
-                    uncompressed:   entry.size(),
-                    compression:    entry.compression(),
+            let canonical_abs;
+            let mut respelled = false;
+            if case_insensitive {
+                canonical_abs = match zro.case_canonical(abs, is_dir) {
+                    Ok(c)   => c,
+                    Err(e)  => skip!(i, name, ZipWarningKind::CaseCollision, e),
                 };
-                if zro.files.insert(abs.into(), entry).is_some() { continue 'files; } // already inserted
-            } else if entry.is_dir() {
+                respelled = canonical_abs != abs;
+                abs = &canonical_abs[..];
+            }
+
+            if !is_dir {
+                let mut entry = match FileEntry::from_record(record, keep_extra_fields) {
+                    Some(entry) => entry,
+                    None        => skip!(i, name, ZipWarningKind::InvalidAesExtraField, Error::unsupported("vfs-zip doesn't support AES encrypted entries without a valid 0x9901 extra field")),
+                };
+                entry.raw_name = raw_name;
+                if keep_extra_fields {
+                    match local_extra_field(&zro.io, record.header_offset) {
+                        Ok(local)   => entry.local_extra = local,
+                        Err(err)    => skip!(i, name, ZipWarningKind::Unreadable(err.to_string()), Error::io(err)),
+                    }
+                }
+                // (existing entry's name had backslashes, existing entry's name differs from this one's)
+                let collision = zro.files.get(abs).map(|existing| {
+                    let existing_name = existing.raw_name.as_ref().map_or(abs, |n| &n[..]);
+                    (existing_name.contains('\\'), existing_name != name)
+                });
+                if let Some((existing_backslashes, renamed)) = collision {
+                    if existing_backslashes || name.contains('\\') {
+                        skip!(i, name, ZipWarningKind::BackslashCollision, Error::unsupported("vfs-zip found a backslash path that collides with another entry after conversion"));
+                    }
+                    let kind = if respelled { ZipWarningKind::CaseCollision } else if renamed { ZipWarningKind::NormalizationCollision } else { ZipWarningKind::Duplicate };
+                    match duplicates {
+                        ZipDuplicatePolicy::Error       => skip!(i, name, kind, Error::unsupported("vfs-zip found multiple entries with the same path (see ZipReadOnlyOptions::duplicates)")),
+                        ZipDuplicatePolicy::FirstWins   => {},
+                        ZipDuplicatePolicy::LastWins    => { zro.files.insert(abs.into(), entry); },
+                    }
+                    zro.warnings.push(ZipWarning { index: i, name: name.into(), kind });
+                    continue 'files; // already inserted into dirs
+                }
+                if case_insensitive { add_folded(&mut zro.folded, abs); }
+                zro.files.insert(abs.into(), entry);
+            } else {
+                if case_insensitive { add_folded(&mut zro.folded, abs); }
                 zro.dirs.entry(abs.into()).or_default();
+                if !record.comment.is_empty() || raw_name.is_some() {
+                    let dir = zro.dir_entries.entry(abs.into()).or_default();
+                    dir.comment = record.comment.to_vec();
+                    dir.raw_name = raw_name;
+                }
             }
 
             while let Some(slash) = abs.rfind('/') {
@@ -88,7 +374,72 @@ impl<IO: Clone + ReadAt> ZipReadOnly<IO> {
             root.insert(abs.into());
         }
 
-        std::mem::drop(archive); // unlock
         Ok(zro)
     }
 }
+
+/// `orig` -> file path relative to the archive root, or [None] if `orig` isn't a valid file path
+pub(crate) fn normalize_file(orig: &str) -> Option<&str> {
+    if orig.contains('\\') || orig.ends_with('/') {
+        return None; // Invalid path for file
+    }
+    let path = if orig.starts_with('/') { &orig[1..] } else { orig };
+    if path.split('/').any(|c| c == "" || c == "." || c == "..") {
+        return None;
+    }
+    Some(path)
+}
+
+/// `orig` -> (path relative to the archive root, must be a directory), or [None] if `orig` isn't a valid path
+pub(crate) fn normalize_path_dir(orig: &str) -> Option<(&str, bool)> {
+    if orig == "" || orig == "/" {
+        Some(("", true)) // root dir
+    } else if orig.ends_with('/') {
+        Some((normalize_file(&orig[..orig.len()-1])?, true))
+    } else {
+        Some((normalize_file(orig)?, false))
+    }
+}
+
+/// Decode an entry name: UTF-8 (lossy) if flagged, otherwise via `decoder` if provided, or CP437.
+///
+/// A valid Info-ZIP Unicode Path extra field (0x7075: u8 version 1, u32 CRC32 of the raw name, UTF-8 name) takes
+/// priority over everything else.
+pub(super) fn decode_name<'r>(record: &CentralRecord<'r>, decoder: Option<&FilenameDecoder>) -> Cow<'r, str> {
+    const FLAG_UTF8 : u16 = 1 << 11;
+    if let Some(field) = extra_field(record.extra, 0x7075).filter(|f| f.len() >= 5 && f[0] == 1) {
+        let crc32 = u32::from_le_bytes([field[1], field[2], field[3], field[4]]);
+        if crc32 == crc32fast::hash(record.name) {
+            if let Ok(name) = std::str::from_utf8(&field[5..]) { return Cow::Borrowed(name) }
+        }
+    }
+    if record.flags & FLAG_UTF8 != 0       { return String::from_utf8_lossy(record.name) }
+    if let Some(decoder) = decoder          { return Cow::Owned((decoder.0)(record.name)) }
+    if record.name.is_ascii()               { return Cow::Borrowed(std::str::from_utf8(record.name).unwrap()) }
+    Cow::Owned(decode_cp437(record.name))
+}
+
+/// Record the case folded spellings of `abs` and all of its parent directories
+fn add_folded(folded: &mut BTreeMap<String, String>, abs: &str) {
+    let ends = abs.match_indices('/').map(|(i, _)| i).chain(std::iter::once(abs.len()));
+    for end in ends {
+        let prefix = &abs[..end];
+        folded.entry(fold_case(prefix)).or_insert_with(|| prefix.into());
+    }
+}
+
+/// Check an entry name (with any backslashes already converted) for paths vfs-zip can't represent, returning why not
+pub(super) fn invalid_name(name: &str) -> Option<(ZipWarningKind, Error)> {
+    if name.contains('\\')                        { return Some((ZipWarningKind::Backslash, Error::unsupported("vfs-zip doesn't support zip archives containing backslashes in paths (see ZipReadOnlyOptions::backslash_separators)"))) }
+    if name.contains("//")                          { return Some((ZipWarningKind::EmptyComponent, Error::unsupported("vfs-zip doesn't support zip archives containing 0-length directory names"))) }
+    if Path::new(name.trim_end_matches('/')).is_absolute() { return Some((ZipWarningKind::AbsolutePath, Error::unsupported("vfs-zip doesn't support zip archives containing absolute paths"))) }
+    None
+}
+
+/// Read the extra field of the local file header at `header_offset`
+pub(super) fn local_extra_field(io: &impl ReadAt, header_offset: u64) -> std::io::Result<Vec<u8>> {
+    let local = read_local_header(io, header_offset)?;
+    let mut extra = vec![0u8; local.extra_len as usize];
+    io.read_exact_at(&mut extra[..], header_offset + local.size() - local.extra_len as u64)?;
+    Ok(extra)
+}
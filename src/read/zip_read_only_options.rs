@@ -0,0 +1,248 @@
+use std::fmt::{self, Debug, Formatter};
+use std::sync::Arc;
+
+
+
+const KB : u64 = 1024;
+const MB : u64 = 1024 * KB;
+const GB : u64 = 1024 * MB;
+
+
+
+/// How [ZipReadOnly](crate::ZipReadOnly) handles multiple entries with the same path.  See [ZipReadOnlyOptions::duplicates].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ZipDuplicatePolicy {
+    /// Keep the first entry, ignoring later ones
+    FirstWins,
+
+    /// Keep the last entry, like most unzip tools (e.g. for archives updated in place by `zip -u` or jar patching)
+    LastWins,
+
+    /// Treat duplicates as errors
+    Error,
+}
+
+
+
+/// Options controlling how a [ZipReadOnly](crate::ZipReadOnly) indexes and reads a zip archive.
+///
+/// ```rust
+/// # use vfs_zip::*;
+/// let options = ZipReadOnlyOptions::strict()
+///     .prefer_in_memory_limit(64 * 1024)
+///     .allow_in_memory_limit(16 * 1024 * 1024);
+/// ```
+#[derive(Clone, Debug)]
+pub struct ZipReadOnlyOptions {
+    pub(crate) ignore_file_errors:      bool,
+    pub(crate) limit_prefer_in_memory:  u64,
+    pub(crate) limit_allow_in_memory:   u64,
+    pub(crate) verify_crc32:            bool,
+    pub(crate) deflate_checkpoint_interval: u64,
+    pub(crate) password:                Option<PasswordCallback>,
+    pub(crate) follow_symlinks:         bool,
+    pub(crate) keep_extra_fields:       bool,
+    pub(crate) case_insensitive:        bool,
+    pub(crate) backslash_separators:    bool,
+    pub(crate) duplicates:              ZipDuplicatePolicy,
+    pub(crate) filename_decoder:        Option<FilenameDecoder>,
+    pub(crate) unicode_nfc:             bool,
+    pub(crate) lazy_index:              bool,
+}
+
+/// Looks up the password of an encrypted file entry by path
+#[derive(Clone)]
+#[allow(clippy::type_complexity)]
+pub(crate) struct PasswordCallback(pub(crate) Arc<dyn Fn(&str) -> Option<Vec<u8>> + Send + Sync>);
+
+impl Debug for PasswordCallback {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result { write!(fmt, "PasswordCallback") }
+}
+
+/// Decodes the raw bytes of entry names that aren't flagged as UTF-8
+#[derive(Clone)]
+#[allow(clippy::type_complexity)]
+pub(crate) struct FilenameDecoder(pub(crate) Arc<dyn Fn(&[u8]) -> String + Send + Sync>);
+
+impl Debug for FilenameDecoder {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result { write!(fmt, "FilenameDecoder") }
+}
+
+impl Default for ZipReadOnlyOptions {
+    fn default() -> Self { Self::strict() }
+}
+
+impl ZipReadOnlyOptions {
+    /// Options matching [ZipReadOnly::new_strict](crate::ZipReadOnly::new_strict):
+    /// Any archive errors (including unsupported paths) will result in errors.
+    pub fn strict() -> Self {
+        Self {
+            ignore_file_errors:     false,
+            limit_prefer_in_memory: 1*KB,
+            limit_allow_in_memory:  1*GB,
+            verify_crc32:           true,
+            deflate_checkpoint_interval: 4*MB,
+            password:               None,
+            follow_symlinks:        false,
+            keep_extra_fields:      false,
+            case_insensitive:       false,
+            backslash_separators:   false,
+            duplicates:             ZipDuplicatePolicy::Error,
+            filename_decoder:       None,
+            unicode_nfc:            false,
+            lazy_index:             false,
+        }
+    }
+
+    /// Options matching [ZipReadOnly::new_relaxed](crate::ZipReadOnly::new_relaxed):
+    /// Some archive errors (such as unsupported paths) will be ignored.
+    pub fn relaxed() -> Self { Self::strict().ignore_file_errors(true) }
+
+    /// Ignore (skip) entries with unsupported paths or other per-file errors instead of failing to open the archive.
+    pub fn ignore_file_errors(mut self, ignore: bool) -> Self { self.ignore_file_errors = ignore; self }
+
+    /// At or below this file size (in bytes), read files into memory up front instead of decompressing them straight from disk.
+    ///
+    /// Defaults to 1 KB.
+    pub fn prefer_in_memory_limit(mut self, bytes: u64) -> Self { self.limit_prefer_in_memory = bytes; self }
+
+    /// At or above this file size (in bytes), fail to read files that would require reading into memory first.
+    ///
+    /// Defaults to 1 GB.
+    pub fn allow_in_memory_limit(mut self, bytes: u64) -> Self { self.limit_allow_in_memory = bytes; self }
+
+    /// Verify the CRC32 of opened files once they've been read to EOF, failing the final read with [InvalidData] on a mismatch.
+    ///
+    /// Defaults to `true`.  Disabling this may be useful for hot paths that already trust the archive.
+    ///
+    /// [InvalidData]:  std::io::ErrorKind::InvalidData
+    pub fn verify_crc32(mut self, verify: bool) -> Self { self.verify_crc32 = verify; self }
+
+    /// Record a checkpoint (~32 KB of decompressor state) roughly every `bytes` of decompressed output while reading
+    /// deflated files, to make seeking within them cheaper.  Deflated files are streamed through [flate2] until they're
+    /// first seeked backwards, and only record checkpoints after that.  Checkpoints are shared between every concurrent
+    /// open of the same file, and dropped once they're all closed.
+    ///
+    /// [flate2]:   https://docs.rs/flate2/
+    ///
+    /// Defaults to 4 MB.  0 disables checkpoints: seeking backwards will then restart decompression from the beginning.
+    pub fn deflate_checkpoint_interval(mut self, bytes: u64) -> Self { self.deflate_checkpoint_interval = bytes; self }
+
+    /// Decrypt encrypted file entries with `password`.
+    ///
+    /// Opening an encrypted file without a password, or with the wrong password, fails with [PermissionDenied].
+    ///
+    /// [PermissionDenied]: std::io::ErrorKind::PermissionDenied
+    pub fn password(self, password: impl Into<Vec<u8>>) -> Self {
+        let password = password.into();
+        self.password_callback(move |_path| Some(password.clone()))
+    }
+
+    /// Decrypt encrypted file entries with the password returned by `callback(path)`, where `path` is the file's path
+    /// within the archive (without a leading `/`.)  Returning [None] is treated like having no password for that file.
+    ///
+    /// ```rust
+    /// # use vfs_zip::*;
+    /// let options = ZipReadOnlyOptions::strict().password_callback(|path| match path {
+    ///     "secrets/a.txt" => Some(b"hunter2".to_vec()),
+    ///     _               => None,
+    /// });
+    /// ```
+    pub fn password_callback(mut self, callback: impl Fn(&str) -> Option<Vec<u8>> + Send + Sync + 'static) -> Self {
+        self.password = Some(PasswordCallback(Arc::new(callback)));
+        self
+    }
+
+    /// Resolve symlinks within the archive when opening files, reading metadata, or listing directories.
+    ///
+    /// Defaults to `false`, where symlinks are treated as regular files containing their target path (see
+    /// [ZipReadOnly::read_link](crate::ZipReadOnly::read_link).)  Links that escape the archive root (via `..` or an
+    /// absolute target) or that form a loop fail with an error instead of being followed.
+    pub fn follow_symlinks(mut self, follow: bool) -> Self { self.follow_symlinks = follow; self }
+
+    /// Keep every file's raw central directory and local header extra fields in memory, for
+    /// [ZipMetadata::extra_fields](crate::ZipMetadata::extra_fields) and
+    /// [ZipMetadata::local_extra_fields](crate::ZipMetadata::local_extra_fields).
+    ///
+    /// Defaults to `false` to save memory.  Enabling this also reads every local file header while indexing (or, with
+    /// [lazy_index](Self::lazy_index), while calling [ZipReadOnly::zip_metadata](crate::ZipReadOnly::zip_metadata).)
+    pub fn keep_extra_fields(mut self, keep: bool) -> Self { self.keep_extra_fields = keep; self }
+
+    /// Look up paths case-insensitively (using Unicode simple case folding), so `Textures/Foo.PNG` finds `textures/foo.png`.
+    /// Exact matches are always preferred, and [read_dir] lists names as spelled in the archive.
+    ///
+    /// Directories whose names differ only by case are merged.  Files whose paths differ only by case are duplicates,
+    /// handled according to [duplicates](Self::duplicates).
+    ///
+    /// Defaults to `false`.
+    ///
+    /// [read_dir]:             https://docs.rs/vfs/0.4/vfs/filesystem/trait.FileSystem.html#tymethod.read_dir
+    pub fn case_insensitive(mut self, case_insensitive: bool) -> Self { self.case_insensitive = case_insensitive; self }
+
+    /// Unicode NFC normalize entry names while indexing, and paths while looking them up, so `café.txt` matches
+    /// regardless of whether either was written decomposed (NFD, as macOS does) or precomposed (NFC.)
+    /// [read_dir] lists the normalized names, while the originals remain available via [ZipMetadata::raw_name].
+    ///
+    /// Files whose names normalize to the same path are duplicates, handled according to [duplicates](Self::duplicates).
+    ///
+    /// Defaults to `false`.
+    ///
+    /// [read_dir]:             https://docs.rs/vfs/0.4/vfs/filesystem/trait.FileSystem.html#tymethod.read_dir
+    /// [ZipMetadata::raw_name]: crate::ZipMetadata::raw_name
+    #[cfg(feature = "unicode-nfc")]
+    pub fn unicode_nfc(mut self, nfc: bool) -> Self { self.unicode_nfc = nfc; self }
+
+    /// Treat `\` in entry names as a directory separator, as written by some old Windows tools, so `foo\bar.txt` is
+    /// readable as `foo/bar.txt`.  The original name remains available via [ZipMetadata::raw_name](crate::ZipMetadata::raw_name).
+    ///
+    /// Converted files that collide with other files are errors, or skipped with [ignore_file_errors](Self::ignore_file_errors).
+    ///
+    /// Defaults to `false`, where entries containing `\` are errors, or skipped with [ignore_file_errors](Self::ignore_file_errors).
+    pub fn backslash_separators(mut self, convert: bool) -> Self { self.backslash_separators = convert; self }
+
+    /// How to handle multiple file entries with the same path.
+    ///
+    /// Defaults to [ZipDuplicatePolicy::Error], since archives that show different contents to different tools are a
+    /// known attack vector.  With [ignore_file_errors](Self::ignore_file_errors) (as in [relaxed](Self::relaxed) mode),
+    /// that instead skips later duplicates, like [ZipDuplicatePolicy::FirstWins].
+    /// Either way, duplicates are listed by [ZipReadOnly::warnings](crate::ZipReadOnly::warnings).
+    pub fn duplicates(mut self, policy: ZipDuplicatePolicy) -> Self { self.duplicates = policy; self }
+
+    /// Keep the raw central directory in memory, indexed by a compact table of record offsets sorted by path, instead
+    /// of building maps of every file and directory up front.  This makes opening archives with millions of entries
+    /// much faster and cheaper, at the cost of decoding entries on every lookup, and computing [read_dir] results on demand.
+    ///
+    /// Duplicates are still detected while indexing, with the same errors and [warnings](crate::ZipReadOnly::warnings).
+    /// Other entries are only validated once a lookup or listing reaches them: entries the eager index would reject
+    /// (e.g. absolute paths) are skipped, even without [ignore_file_errors](Self::ignore_file_errors), and aren't
+    /// listed by [warnings](crate::ZipReadOnly::warnings).
+    /// [case_insensitive](Self::case_insensitive), [backslash_separators](Self::backslash_separators), and
+    /// `unicode_nfc` are unsupported: [ZipReadOnly::new_with_options](crate::ZipReadOnly::new_with_options) fails if
+    /// they're combined with this.
+    ///
+    /// Defaults to `false`.
+    ///
+    /// [read_dir]:             https://docs.rs/vfs/0.4/vfs/filesystem/trait.FileSystem.html#tymethod.read_dir
+    pub fn lazy_index(mut self, lazy: bool) -> Self { self.lazy_index = lazy; self }
+
+    /// Decode entry names that aren't flagged as UTF-8 with a legacy `encoding` instead of CP437, for archives created
+    /// on e.g. Japanese (Shift-JIS) or Russian (CP866) Windows machines.
+    ///
+    /// ```rust
+    /// # use vfs_zip::*;
+    /// let options = ZipReadOnlyOptions::strict().filename_encoding(encoding_rs::SHIFT_JIS);
+    /// ```
+    #[cfg(feature = "zip-encodings")]
+    pub fn filename_encoding(self, encoding: &'static encoding_rs::Encoding) -> Self {
+        self.filename_decoder(move |raw| encoding.decode_without_bom_handling(raw).0.into_owned())
+    }
+
+    /// Decode entry names that aren't flagged as UTF-8 with `decoder(raw_name)` instead of as CP437.
+    ///
+    /// Names flagged as UTF-8 (general purpose bit 11), or with a valid Info-ZIP Unicode Path extra field (0x7075),
+    /// are always decoded as UTF-8 instead.
+    pub fn filename_decoder(mut self, decoder: impl Fn(&[u8]) -> String + Send + Sync + 'static) -> Self {
+        self.filename_decoder = Some(FilenameDecoder(Arc::new(decoder)));
+        self
+    }
+}
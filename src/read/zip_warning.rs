@@ -0,0 +1,90 @@
+use std::fmt::{self, Display, Formatter};
+
+
+
+/// An entry that [ZipReadOnly](crate::ZipReadOnly) skipped or deduplicated while indexing, as returned by
+/// [ZipReadOnly::warnings](crate::ZipReadOnly::warnings).
+///
+/// Most of these are only possible with [ZipReadOnlyOptions::ignore_file_errors](crate::ZipReadOnlyOptions::ignore_file_errors)
+/// (as in [ZipReadOnly::new_relaxed](crate::ZipReadOnly::new_relaxed)), since they're errors otherwise.
+///
+/// ```rust
+/// # use vfs_zip::*;
+/// # fn log(zip: &ZipReadOnly<std::fs::File>) {
+/// for warning in zip.warnings() {
+///     eprintln!("warning: {}", warning); // e.g. `warning: entry #3 "d//e.txt": contains an empty path component`
+/// }
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ZipWarning {
+    pub(crate) index:   usize,
+    pub(crate) name:    String,
+    pub(crate) kind:    ZipWarningKind,
+}
+
+/// Why an entry was skipped or deduplicated.  See [ZipWarning].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ZipWarningKind {
+    /// The entry couldn't be read (e.g. unsupported features or a corrupt header.)  Skipped.
+    Unreadable(String),
+
+    /// The entry's name contains backslashes (see [ZipReadOnlyOptions::backslash_separators](crate::ZipReadOnlyOptions::backslash_separators).)  Skipped.
+    Backslash,
+
+    /// The entry's name contains an empty path component (`//`).  Skipped.
+    EmptyComponent,
+
+    /// The entry's name is an absolute path.  Skipped.
+    AbsolutePath,
+
+    /// The entry is AES encrypted, but lacks a valid 0x9901 extra field.  Skipped.
+    InvalidAesExtraField,
+
+    /// The entry's path differs only by case from an earlier entry (see [ZipReadOnlyOptions::case_insensitive](crate::ZipReadOnlyOptions::case_insensitive).)
+    /// Skipped if they're of different types (file vs directory), otherwise handled like [Duplicate](Self::Duplicate).
+    CaseCollision,
+
+    /// The entry's path collides with another entry after converting backslashes.  Skipped.
+    BackslashCollision,
+
+    /// The entry's path is the same as an earlier entry's after Unicode normalization (see
+    /// [ZipReadOnlyOptions::unicode_nfc](crate::ZipReadOnlyOptions::unicode_nfc).)  Handled like [Duplicate](Self::Duplicate).
+    NormalizationCollision,
+
+    /// The entry has the same path as an earlier entry.  Whichever entry wins depends on [ZipReadOnlyOptions::duplicates](crate::ZipReadOnlyOptions::duplicates).
+    Duplicate,
+}
+
+impl ZipWarning {
+    /// The index of the entry within the central directory
+    pub fn index(&self) -> usize { self.index }
+
+    /// The entry's name as stored in the archive, or as close as possible if it couldn't be read
+    pub fn name(&self) -> &str { &self.name[..] }
+
+    /// Why the entry was skipped or deduplicated
+    pub fn kind(&self) -> &ZipWarningKind { &self.kind }
+}
+
+impl Display for ZipWarning {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        write!(fmt, "entry #{} {:?}: {}", self.index, self.name, self.kind)
+    }
+}
+
+impl Display for ZipWarningKind {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        match self {
+            ZipWarningKind::Unreadable(err)         => write!(fmt, "unreadable: {}", err),
+            ZipWarningKind::Backslash               => write!(fmt, "contains backslashes"),
+            ZipWarningKind::EmptyComponent          => write!(fmt, "contains an empty path component"),
+            ZipWarningKind::AbsolutePath            => write!(fmt, "is an absolute path"),
+            ZipWarningKind::InvalidAesExtraField    => write!(fmt, "is AES encrypted without a valid 0x9901 extra field"),
+            ZipWarningKind::CaseCollision           => write!(fmt, "differs only by case from an earlier entry"),
+            ZipWarningKind::BackslashCollision      => write!(fmt, "collides with another entry after converting backslashes"),
+            ZipWarningKind::NormalizationCollision  => write!(fmt, "is the same as an earlier entry after Unicode normalization"),
+            ZipWarningKind::Duplicate               => write!(fmt, "duplicates an earlier entry"),
+        }
+    }
+}
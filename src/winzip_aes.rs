@@ -0,0 +1,105 @@
+use aes::cipher::{BlockEncrypt, KeyInit};
+use aes::cipher::generic_array::GenericArray;
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+
+use std::io;
+
+
+
+pub(crate) const AES_EXTRA_FIELD    : u16 = 0x9901;
+pub(crate) const AES_MAC_SIZE       : u64 = 10;
+pub(crate) const AES_VERIFIER_SIZE  : u64 = 2;
+
+pub(crate) type AesMac = Hmac<Sha1>;
+
+/// Keys for WinZip AES encryption (AE-1 / AE-2), derived from a password + salt.
+///
+/// Encrypted file data is laid out as: salt, u16 password verifier, AES-CTR encrypted data, 10 byte HMAC-SHA1 of the
+/// encrypted data.  The real compression method lives in the 0x9901 extra field, while the headers say method 99.
+/// See <https://www.winzip.com/en/support/aes-encryption/>
+pub(crate) struct AesKeys {
+    cipher:     AesCipher,
+    mac:        AesMac,
+    verifier:   [u8; 2],
+}
+
+enum AesCipher {
+    Aes128(aes::Aes128),
+    Aes192(aes::Aes192),
+    Aes256(aes::Aes256),
+}
+
+/// Salt size for an AES strength (1 = AES-128, 2 = AES-192, 3 = AES-256)
+pub(crate) fn aes_salt_size(strength: u8) -> io::Result<u64> {
+    match strength {
+        1 => Ok(8),
+        2 => Ok(12),
+        3 => Ok(16),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidData, "unsupported WinZip AES strength")),
+    }
+}
+
+impl AesKeys {
+    pub fn derive(password: &[u8], salt: &[u8], strength: u8) -> io::Result<Self> {
+        let key_size = match strength { 1 => 16, 2 => 24, 3 => 32, _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "unsupported WinZip AES strength")) };
+        let mut derived = vec![0u8; 2 * key_size + 2];
+        pbkdf2::pbkdf2_hmac::<Sha1>(password, salt, 1000, &mut derived[..]);
+
+        let key = &derived[..key_size];
+        let cipher = match strength {
+            1 => AesCipher::Aes128(aes::Aes128::new(GenericArray::from_slice(key))),
+            2 => AesCipher::Aes192(aes::Aes192::new(GenericArray::from_slice(key))),
+            _ => AesCipher::Aes256(aes::Aes256::new(GenericArray::from_slice(key))),
+        };
+        let mac = <AesMac as Mac>::new_from_slice(&derived[key_size .. 2 * key_size]).expect("HMAC accepts keys of any size");
+        Ok(Self { cipher, mac, verifier: [derived[2 * key_size], derived[2 * key_size + 1]] })
+    }
+
+    pub fn verifier(&self) -> [u8; 2] { self.verifier }
+
+    /// A fresh HMAC-SHA1 for authenticating the encrypted data
+    pub fn mac(&self) -> AesMac { self.mac.clone() }
+
+    /// XOR `buf` with the keystream starting at byte `pos` of the encrypted data.  Encryption and decryption are identical.
+    pub fn apply_keystream(&self, pos: u64, buf: &mut [u8]) {
+        let mut block_index = pos / 16;
+        let mut offset = (pos % 16) as usize;
+        let mut buf = buf;
+        while !buf.is_empty() {
+            let mut block = [0u8; 16];
+            block[..8].copy_from_slice(&(block_index + 1).to_le_bytes()); // little endian counter, starting at 1
+            let block_ga = GenericArray::from_mut_slice(&mut block[..]);
+            match self.cipher {
+                AesCipher::Aes128(ref c) => c.encrypt_block(block_ga),
+                AesCipher::Aes192(ref c) => c.encrypt_block(block_ga),
+                AesCipher::Aes256(ref c) => c.encrypt_block(block_ga),
+            }
+
+            let n = (16 - offset).min(buf.len());
+            for (b, k) in buf[..n].iter_mut().zip(block[offset..].iter()) { *b ^= k; }
+            buf = &mut buf[n..];
+            block_index += 1;
+            offset = 0;
+        }
+    }
+}
+
+/// AES-256 encrypt `data` with a random salt, returning the salt, password verifier, encrypted data, and authentication code
+pub(crate) fn aes_encrypt(password: &[u8], data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut salt = [0u8; 16];
+    getrandom::getrandom(&mut salt[..]).map_err(|e| io::Error::new(io::ErrorKind::Other, format!("unable to generate AES salt: {}", e)))?;
+    let keys = AesKeys::derive(password, &salt[..], 3)?;
+
+    let mut out = Vec::with_capacity(salt.len() + data.len() + (AES_VERIFIER_SIZE + AES_MAC_SIZE) as usize);
+    out.extend_from_slice(&salt[..]);
+    out.extend_from_slice(&keys.verifier()[..]);
+    let start = out.len();
+    out.extend_from_slice(data);
+    keys.apply_keystream(0, &mut out[start..]);
+
+    let mut mac = keys.mac();
+    mac.update(&out[start..]);
+    out.extend_from_slice(&mac.finalize().into_bytes()[..AES_MAC_SIZE as usize]);
+    Ok(out)
+}
@@ -1,6 +1,9 @@
 #[cfg(feature = "vfs04")] mod vfs04;
 
-use crate::Result;
+mod zip_write_only_options;     pub use zip_write_only_options::*;
+mod zip_writer;                 pub(crate) use zip_writer::*;
+
+use crate::{normalize_path_dir, Error, Result};
 
 use std::collections::*;
 use std::fmt::{self, Debug, Formatter};
@@ -16,8 +19,10 @@ pub struct ZipWriteOnly<IO: Write + Seek + Send + 'static> {
 }
 
 struct Imp<IO: Write + Seek + Send + 'static> {
-    writer: zip::write::ZipWriter<IO>,
-    dirs:   BTreeSet<String>,
+    writer:     ZipWriter<IO>,
+    dirs:       BTreeSet<String>,
+    compression: ZipCompression,
+    password:   Option<Vec<u8>>,
 }
 
 impl<IO: Write + Seek + Send> Debug for ZipWriteOnly<IO> {
@@ -30,21 +35,48 @@ impl<IO: Write + Seek + Send> ZipWriteOnly<IO> {
     /// Create a new write-only zip filesystem.
     ///
     /// The underlying I/O will not be closed until the filesystem and all outstanding files are dropped.
-    pub fn new_strong(io: IO) -> Result<Self> { Self::new_impl(io, false) }
+    pub fn new_strong(io: IO) -> Result<Self> { Self::new_with_options(io, ZipWriteOnlyOptions::default()) }
 
     /// Create a new write-only zip filesystem.
     ///
     /// The underlying I/O will be closed when the filesystem is dropped.
     /// Any outstanding files will start generating I/O errors and will not be committed to the .zip
-    pub fn new_weak(io: IO) -> Result<Self> { Self::new_impl(io, true) }
+    pub fn new_weak(io: IO) -> Result<Self> { Self::new_with_options(io, ZipWriteOnlyOptions::default().weak(true)) }
 
-    fn new_impl(io: IO, weak: bool) -> Result<Self> {
+    /// Create a new write-only zip filesystem.
+    /// Strong/weak I/O ownership, compression, etc. are controlled by `options`.
+    pub fn new_with_options(io: IO, options: ZipWriteOnlyOptions) -> Result<Self> {
         Ok(Self {
             imp: Arc::new(Mutex::new(Imp {
-                writer: zip::write::ZipWriter::new(io),
-                dirs:   Default::default(),
+                writer:         ZipWriter::new(io),
+                dirs:           Default::default(),
+                compression:    options.compression,
+                password:       options.password.map(|p| p.0),
             })),
-            weak,
+            weak: options.weak,
         })
     }
+
+    /// Add an extra field with the given `header_id` to the local and central directory headers of the file or
+    /// directory at `path`, as read back by [ZipMetadata::extra_fields](crate::ZipMetadata::extra_fields).
+    ///
+    /// This must be called before the entry is written (before the file returned by `create_file` is dropped), and may
+    /// be called multiple times to add multiple fields.  The Zip64 extra field (0x0001) is reserved for vfs-zip itself.
+    pub fn add_extra_field(&self, path: &str, header_id: u16, data: &[u8]) -> Result<()> {
+        let (path, _dir) = normalize_path_dir(path).ok_or_else(|| Error::io(std::io::Error::new(std::io::ErrorKind::InvalidInput, "invalid path")))?;
+        self.imp.lock().unwrap().writer.add_extra_field(path, header_id, data).map_err(Error::io)
+    }
+
+    /// Set the archive comment, as read back by [ZipReadOnly::comment](crate::ZipReadOnly::comment).
+    /// Comments are limited to 65535 bytes.
+    pub fn set_comment(&self, comment: impl Into<Vec<u8>>) -> Result<()> {
+        self.imp.lock().unwrap().writer.set_comment(comment.into()).map_err(Error::io)
+    }
+
+    /// Set the comment of the file or directory at `path`, as read back by [ZipMetadata::comment](crate::ZipMetadata::comment).
+    /// The entry may be created before or after this call.  Comments are limited to 65535 bytes.
+    pub fn set_entry_comment(&self, path: &str, comment: impl Into<Vec<u8>>) -> Result<()> {
+        let (path, _dir) = normalize_path_dir(path).ok_or_else(|| Error::io(std::io::Error::new(std::io::ErrorKind::InvalidInput, "invalid path")))?;
+        self.imp.lock().unwrap().writer.set_entry_comment(path, comment.into()).map_err(Error::io)
+    }
 }
@@ -2,9 +2,6 @@ use super::*;
 
 use ::vfs04::*;
 
-use ::zip::result::ZipError;
-use ::zip::write::FileOptions;
-
 use std::io::{self, Cursor};
 use std::mem::replace;
 use std::ops::Drop;
@@ -44,14 +41,7 @@ impl<IO: Write + Seek + Send + 'static> FileSystem for ZipWriteOnly<IO> {
             return Ok(());
         }
 
-        match imp.writer.add_directory(path, FileOptions::default()) {
-            Err(ZipError::FileNotFound)             => return Err(VfsError::FileNotFound { path: path.into() }),
-            Err(ZipError::Io(e))                    => return Err(VfsError::IoError(e)),
-            Err(ZipError::InvalidArchive(e))        => return Err(VfsError::IoError(io::Error::new(io::ErrorKind::InvalidData, e))),
-            Err(ZipError::UnsupportedArchive(e))    => return Err(VfsError::IoError(io::Error::new(io::ErrorKind::InvalidData, e))),
-            Err(other)                              => return Err(VfsError::Other { message: other.to_string() }),
-            Ok(())                                  => {},
-        }
+        imp.writer.add_directory(path)?;
         imp.dirs.insert(path.into());
         Ok(())
     }
@@ -134,8 +124,8 @@ impl<IO: Write + Seek + Send> Drop for ZipFileWriter<IO> {
             }
         };
         let mut imp = imp.lock().unwrap();
-        if imp.writer.start_file(path, zip::write::FileOptions::default()).is_err() { return; }
-        let _ = imp.writer.write_all(&buffer[..]);
+        let imp = &mut *imp;
+        let _ = imp.writer.add_file(&path, &buffer[..], imp.compression, imp.password.as_ref().map(|p| &p[..]));
     }
 }
 
@@ -143,6 +133,7 @@ impl<IO: Write + Seek + Send> Drop for ZipFileWriter<IO> {
     use crate::*;
     use super::{VfsError, VfsFileType, VfsPath, VfsResult};
     use std::fs::{create_dir_all, File};
+    use std::io::Write;
 
     #[test] fn copy_early_vfs_zip() {
         let _ = create_dir_all("target/tmp");
@@ -152,6 +143,152 @@ impl<IO: Write + Seek + Send> Drop for ZipFileWriter<IO> {
         assert_eq!(copied, 16);
     }
 
+    #[test] fn round_trip_compression() {
+        let _ = create_dir_all("target/tmp");
+        let mut methods = vec![("stored", ZipCompression::Stored)];
+        #[cfg(feature = "zip-deflate")] methods.push(("deflated", ZipCompression::Deflated));
+        #[cfg(feature = "zip-bzip2")]   methods.push(("bzip2", ZipCompression::Bzip2));
+        #[cfg(feature = "zip-zstd")]    methods.push(("zstd", ZipCompression::Zstd));
+
+        let text = "Hello, world!\n".repeat(1000);
+        for &(name, compression) in methods.iter() {
+            let path = format!("target/tmp/round-trip-{}.zip", name);
+            {
+                let options = ZipWriteOnlyOptions::default().compression(compression);
+                let dst = VfsPath::new(ZipWriteOnly::new_with_options(File::create(&path).unwrap(), options).unwrap());
+                dst.join("dir").unwrap().create_dir().unwrap();
+                dst.join("dir/small.txt").unwrap().create_file().unwrap().write_all(b"small").unwrap();
+                dst.join("dir/large.txt").unwrap().create_file().unwrap().write_all(text.as_bytes()).unwrap();
+            }
+
+            let zip = ZipReadOnly::new_strict(File::open(&path).unwrap()).unwrap();
+            let small = zip.zip_metadata("dir/small.txt").unwrap();
+            assert_eq!(small.host_system(), 3); // Unix
+            assert_eq!(small.unix_mode(), Some(0o100644));
+
+            let src = VfsPath::new(zip);
+            assert_eq!(src.join("dir/small.txt").unwrap().read_to_string().unwrap(), "small");
+            assert_eq!(src.join("dir/large.txt").unwrap().read_to_string().unwrap(), text);
+            assert_eq!(src.join("dir").unwrap().metadata().unwrap().file_type, VfsFileType::Directory);
+        }
+    }
+
+    #[test] fn round_trip_comments() {
+        let _ = create_dir_all("target/tmp");
+        let path = "target/tmp/round-trip-comments.zip";
+        {
+            let zip = ZipWriteOnly::new_strong(File::create(path).unwrap()).unwrap();
+            zip.set_comment("build 1234").unwrap();
+            zip.set_entry_comment("/dir/a.txt", "set before writing").unwrap();
+            assert!(zip.set_entry_comment("../a.txt", "").is_err());
+            assert!(zip.set_comment(vec![b'!'; 0x10000]).is_err());
+            let dst = VfsPath::new(zip);
+            dst.join("dir").unwrap().create_dir().unwrap();
+            dst.join("dir/a.txt").unwrap().create_file().unwrap().write_all(b"a").unwrap();
+            dst.join("dir/b.txt").unwrap().create_file().unwrap().write_all(b"b").unwrap();
+        }
+
+        let zip = ZipReadOnly::new_strict(File::open(path).unwrap()).unwrap();
+        assert_eq!(zip.comment(), b"build 1234");
+        assert_eq!(zip.zip_metadata("dir/a.txt").unwrap().comment(), b"set before writing");
+        assert_eq!(zip.zip_metadata("dir/b.txt").unwrap().comment(), b"");
+        assert_eq!(zip.zip_metadata("dir").unwrap().comment(), b"");
+        assert_eq!(VfsPath::new(zip).join("dir/a.txt").unwrap().read_to_string().unwrap(), "a");
+    }
+
+    #[test] fn round_trip_extra_fields() {
+        let _ = create_dir_all("target/tmp");
+        let path = "target/tmp/round-trip-extra-fields.zip";
+        {
+            let zip = ZipWriteOnly::new_strong(File::create(path).unwrap()).unwrap();
+            zip.add_extra_field("vendor.txt", 0xCAFE, b"id=42").unwrap();
+            zip.add_extra_field("/vendor.txt", 0xCAFF, b"").unwrap();
+            assert!(zip.add_extra_field("vendor.txt", 0x0001, b"").is_err()); // Zip64 is reserved
+            let dst = VfsPath::new(zip);
+            dst.join("vendor.txt").unwrap().create_file().unwrap().write_all(b"vendor").unwrap();
+            dst.join("plain.txt").unwrap().create_file().unwrap().write_all(b"plain").unwrap();
+        }
+
+        let lean = ZipReadOnly::new_strict(File::open(path).unwrap()).unwrap();
+        assert_eq!(lean.zip_metadata("vendor.txt").unwrap().extra_fields().count(), 0); // not kept by default
+
+        let zip = ZipReadOnly::new_with_options(File::open(path).unwrap(), ZipReadOnlyOptions::strict().keep_extra_fields(true)).unwrap();
+        let vendor = zip.zip_metadata("vendor.txt").unwrap();
+        let expected = vec![(0xCAFE, &b"id=42"[..]), (0xCAFF, &b""[..])];
+        let not_timestamps = |&(id, _): &(u16, &[u8])| id != 0x5455; // written with zip-time
+        assert_eq!(vendor.extra_fields().filter(not_timestamps).collect::<Vec<_>>(), expected);
+        assert_eq!(vendor.local_extra_fields().filter(not_timestamps).collect::<Vec<_>>(), expected);
+        assert_eq!(zip.zip_metadata("plain.txt").unwrap().extra_fields().filter(not_timestamps).count(), 0);
+        assert_eq!(VfsPath::new(zip).join("vendor.txt").unwrap().read_to_string().unwrap(), "vendor");
+    }
+
+    #[test] fn utf8_flag() {
+        let _ = create_dir_all("target/tmp");
+        let path = "target/tmp/utf8-flag.zip";
+        {
+            let dst = VfsPath::new(ZipWriteOnly::new_strong(File::create(path).unwrap()).unwrap());
+            dst.join("café").unwrap().create_dir().unwrap();
+            dst.join("café/naïve.txt").unwrap().create_file().unwrap().write_all(b"utf8").unwrap();
+            dst.join("plain.txt").unwrap().create_file().unwrap().write_all(b"ascii").unwrap();
+        }
+
+        // Only names not flagged as UTF-8 are passed to the decoder
+        let options = ZipReadOnlyOptions::strict().filename_decoder(|raw| String::from_utf8_lossy(raw).to_uppercase());
+        let src = VfsPath::new(ZipReadOnly::new_with_options(File::open(path).unwrap(), options).unwrap());
+        assert_eq!(src.join("café/naïve.txt").unwrap().read_to_string().unwrap(), "utf8");
+        assert_eq!(src.join("PLAIN.TXT").unwrap().read_to_string().unwrap(), "ascii");
+    }
+
+    #[cfg(feature = "zip-time")] #[test] fn timestamps() {
+        use std::time::{Duration, SystemTime};
+        let _ = create_dir_all("target/tmp");
+        let path = "target/tmp/timestamps.zip";
+        let before = SystemTime::now() - Duration::from_secs(1);
+        {
+            let dst = VfsPath::new(ZipWriteOnly::new_strong(File::create(path).unwrap()).unwrap());
+            dst.join("now.txt").unwrap().create_file().unwrap().write_all(b"now").unwrap();
+        }
+        let after = SystemTime::now() + Duration::from_secs(1);
+
+        // The extended timestamp extra field is preferred over the (UTC) MS-DOS timestamp, and has 1 second precision
+        let zip = ZipReadOnly::new_with_options(File::open(path).unwrap(), ZipReadOnlyOptions::strict().keep_extra_fields(true)).unwrap();
+        let now = zip.zip_metadata("now.txt").unwrap();
+        assert_eq!(now.extra_fields().filter(|&(id, _)| id == 0x5455).count(), 1);
+        assert_eq!(now.local_extra_fields().filter(|&(id, _)| id == 0x5455).count(), 1);
+        let modified = now.modified().unwrap();
+        assert!(before <= modified && modified <= after);
+    }
+
+    #[cfg(feature = "zip-aes")] #[test] fn round_trip_aes() {
+        let _ = create_dir_all("target/tmp");
+        let path = "target/tmp/round-trip-aes.zip";
+        let text = "Hello, world!\n".repeat(1000);
+        {
+            let options = ZipWriteOnlyOptions::default().password("hunter2");
+            let dst = VfsPath::new(ZipWriteOnly::new_with_options(File::create(path).unwrap(), options).unwrap());
+            dst.join("small.txt").unwrap().create_file().unwrap().write_all(b"small").unwrap();
+            dst.join("large.txt").unwrap().create_file().unwrap().write_all(text.as_bytes()).unwrap();
+        }
+
+        let options = ZipReadOnlyOptions::strict().password("hunter2");
+        let src = VfsPath::new(ZipReadOnly::new_with_options(File::open(path).unwrap(), options).unwrap());
+        assert_eq!(src.join("small.txt").unwrap().read_to_string().unwrap(), "small");
+        assert_eq!(src.join("large.txt").unwrap().read_to_string().unwrap(), text);
+
+        let options = ZipReadOnlyOptions::strict().password("letmein");
+        let src = VfsPath::new(ZipReadOnly::new_with_options(File::open(path).unwrap(), options).unwrap());
+        assert!(src.join("small.txt").unwrap().read_to_string().is_err());
+
+        // Corrupt the first byte of small.txt's encrypted data: the authentication code should catch it
+        let mut zip = std::fs::read(path).unwrap();
+        let (name_len, extra_len) = (u16::from_le_bytes([zip[26], zip[27]]) as usize, u16::from_le_bytes([zip[28], zip[29]]) as usize);
+        let data_start = 30 + name_len + extra_len + 16 + 2; // local header, name, extra fields (AES, maybe timestamps), salt, password verifier
+        zip[data_start] ^= 1;
+        let options = ZipReadOnlyOptions::strict().password("hunter2");
+        let src = VfsPath::new(ZipReadOnly::new_with_options(zip, options).unwrap());
+        assert!(src.join("small.txt").unwrap().read_to_string().is_err());
+    }
+
     /// NOTE: https://github.com/MaulingMonkey/vfs-zip/issues/1
     fn copy_dir_merge(dst: &VfsPath, src: &VfsPath) -> VfsResult<usize> {
         let mut n = 0;
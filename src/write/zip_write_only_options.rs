@@ -0,0 +1,65 @@
+use std::fmt::{self, Debug, Formatter};
+
+
+
+/// Compression method used by [ZipWriteOnly](crate::ZipWriteOnly) for new files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ZipCompression {
+    /// Store files without any compression
+    Stored,
+
+    /// Compress files with Deflate (method 8)
+    #[cfg(feature = "zip-deflate")] Deflated,
+
+    /// Compress files with bzip2 (method 12)
+    #[cfg(feature = "zip-bzip2")] Bzip2,
+
+    /// Compress files with Zstandard (method 93)
+    #[cfg(feature = "zip-zstd")] Zstd,
+}
+
+impl Default for ZipCompression {
+    #[cfg(feature = "zip-deflate")]     fn default() -> Self { ZipCompression::Deflated }
+    #[cfg(not(feature = "zip-deflate"))] fn default() -> Self { ZipCompression::Stored }
+}
+
+
+
+/// Options controlling how a [ZipWriteOnly](crate::ZipWriteOnly) writes a zip archive.
+///
+/// ```rust
+/// # use vfs_zip::*;
+/// let options = ZipWriteOnlyOptions::default().weak(true).compression(ZipCompression::Stored);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ZipWriteOnlyOptions {
+    pub(crate) weak:        bool,
+    pub(crate) compression: ZipCompression,
+    pub(crate) password:    Option<Password>,
+}
+
+/// Password for encrypting new files (kept out of [Debug] output)
+#[derive(Clone)]
+pub(crate) struct Password(pub(crate) Vec<u8>);
+
+impl Debug for Password {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result { write!(fmt, "Password") }
+}
+
+impl ZipWriteOnlyOptions {
+    /// If `true`, behave like [ZipWriteOnly::new_weak](crate::ZipWriteOnly::new_weak), otherwise like [ZipWriteOnly::new_strong](crate::ZipWriteOnly::new_strong).
+    ///
+    /// Defaults to `false`.
+    pub fn weak(mut self, weak: bool) -> Self { self.weak = weak; self }
+
+    /// Compression method for new files.
+    ///
+    /// Defaults to [ZipCompression::Deflated] if the `zip-deflate` feature is enabled, [ZipCompression::Stored] otherwise.
+    pub fn compression(mut self, compression: ZipCompression) -> Self { self.compression = compression; self }
+
+    /// Encrypt new files with WinZip AES-256 (AE-2) using `password`.
+    ///
+    /// Defaults to no encryption.
+    #[cfg(feature = "zip-aes")]
+    pub fn password(mut self, password: impl Into<Vec<u8>>) -> Self { self.password = Some(Password(password.into())); self }
+}
@@ -0,0 +1,373 @@
+use super::ZipCompression;
+use crate::extra_field;
+#[cfg(feature = "zip-aes")] use crate::{aes_encrypt, AES_EXTRA_FIELD};
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::io::{self, Seek, SeekFrom, Write};
+
+
+
+/// Minimal zip archive writer.
+///
+/// Unlike `zip::write::ZipWriter`, this only accepts complete files (which [ZipWriteOnly](crate::ZipWriteOnly) buffers
+/// anyways), so sizes and CRCs are always known up front: no data descriptors, no seeking back to patch local headers,
+/// and Zip64 extra fields are only emitted when actually necessary.
+pub(crate) struct ZipWriter<IO: Write + Seek> {
+    io:         IO,
+    entries:    Vec<CentralEntry>,
+    finished:   bool,
+    comment:    Vec<u8>,
+    comments:   BTreeMap<String, Vec<u8>>, // path (without trailing '/') -> entry comment
+    extras:     BTreeMap<String, Vec<u8>>, // path (without trailing '/') -> caller supplied extra fields for entries not yet added
+}
+
+struct CentralEntry {
+    name:                   Vec<u8>,
+    flags:                  u16,
+    method:                 u16,
+    version_needed:         u16,
+    dos_time:               u16,
+    dos_date:               u16,
+    crc32:                  u32,
+    compressed:             u64,
+    uncompressed:           u64,
+    header_offset:          u64,
+    external_attributes:    u32,
+    extra:                  Vec<u8>, // extra fields other than Zip64
+}
+
+/// Everything about a new entry that isn't decided by [ZipWriter::add_entry] itself
+struct NewEntry<'a> {
+    name:                   Vec<u8>,
+    flags:                  u16,
+    method:                 u16,
+    crc32:                  u32,
+    uncompressed:           u64,
+    compressed:             &'a [u8],
+    external_attributes:    u32,
+    extra:                  Vec<u8>,
+}
+
+const LOCAL_FILE_HEADER_SIGNATURE       : u32 = 0x04034b50;
+const CENTRAL_DIRECTORY_HEADER_SIGNATURE: u32 = 0x02014b50;
+const ZIP64_EOCD_SIGNATURE              : u32 = 0x06064b50;
+const ZIP64_EOCD_LOCATOR_SIGNATURE      : u32 = 0x07064b50;
+const EOCD_SIGNATURE                    : u32 = 0x06054b50;
+
+const VERSION_MADE_BY   : u16 = (3 << 8) | 63; // Unix, spec 6.3
+const FLAG_UTF8         : u16 = 1 << 11;
+const EXTENDED_TIMESTAMP: u16 = 0x5455;
+
+const METHOD_STORED     : u16 = 0;
+#[cfg(feature = "zip-deflate")]
+const METHOD_DEFLATED   : u16 = 8;
+const METHOD_BZIP2      : u16 = 12;
+const METHOD_ZSTD       : u16 = 93;
+const METHOD_AES        : u16 = 99;
+
+impl<IO: Write + Seek> ZipWriter<IO> {
+    pub fn new(io: IO) -> Self {
+        Self { io, entries: Vec::new(), finished: false, comment: Vec::new(), comments: Default::default(), extras: Default::default() }
+    }
+
+    /// Set the archive comment, written into the end of central directory record
+    pub fn set_comment(&mut self, comment: Vec<u8>) -> io::Result<()> {
+        if comment.len() > 0xFFFF { return Err(io::Error::new(io::ErrorKind::InvalidInput, "comment too long for a zip archive")) }
+        self.comment = comment;
+        Ok(())
+    }
+
+    /// Set the comment of the file or directory at `path`, which may be added before or after this call.
+    pub fn set_entry_comment(&mut self, path: &str, comment: Vec<u8>) -> io::Result<()> {
+        if comment.len() > 0xFFFF { return Err(io::Error::new(io::ErrorKind::InvalidInput, "comment too long for a zip archive")) }
+        self.comments.insert(path.into(), comment);
+        Ok(())
+    }
+
+    pub fn add_directory(&mut self, path: &str) -> io::Result<()> {
+        let name = format!("{}/", path);
+        self.add_entry(NewEntry {
+            name:                   name.into_bytes(),
+            flags:                  0,
+            method:                 METHOD_STORED,
+            crc32:                  0,
+            uncompressed:           0,
+            compressed:             &[],
+            external_attributes:    0o40755 << 16 | 0x10, // S_IFDIR | rwxr-xr-x, FILE_ATTRIBUTE_DIRECTORY
+            extra:                  Vec::new(),
+        })
+    }
+
+    /// Append an extra field to the (local and central) headers of the file or directory at `path`, which must not have
+    /// been added yet.
+    pub fn add_extra_field(&mut self, path: &str, header_id: u16, data: &[u8]) -> io::Result<()> {
+        if header_id == 0x0001 { return Err(io::Error::new(io::ErrorKind::InvalidInput, "the Zip64 extra field is written automatically when necessary")) }
+        if data.len() > 0xFFFF { return Err(io::Error::new(io::ErrorKind::InvalidInput, "extra field too long for a zip archive")) }
+        if self.entries.iter().any(|e| std::str::from_utf8(&e.name[..]).map_or(false, |n| n.trim_end_matches('/') == path)) {
+            return Err(io::Error::new(io::ErrorKind::Other, "entry already written, extra fields must be added before the file is closed"));
+        }
+        let extra = self.extras.entry(path.into()).or_default();
+        extra.extend_from_slice(&header_id.to_le_bytes());
+        extra.extend_from_slice(&(data.len() as u16).to_le_bytes());
+        extra.extend_from_slice(data);
+        Ok(())
+    }
+
+    /// Add a file, AES-256 encrypting it if `password` is provided
+    pub fn add_file(&mut self, path: &str, data: &[u8], compression: ZipCompression, password: Option<&[u8]>) -> io::Result<()> {
+        let crc32 = crc32fast::hash(data);
+        let (method, compressed) = compress(data, compression)?;
+        let entry = NewEntry {
+            name:                   path.as_bytes().into(),
+            flags:                  0,
+            method,
+            crc32,
+            uncompressed:           data.len() as u64,
+            compressed:             &compressed[..],
+            external_attributes:    0o100644 << 16, // S_IFREG | rw-r--r--
+            extra:                  Vec::new(),
+        };
+
+        match password {
+            None => self.add_entry(entry),
+            #[cfg(feature = "zip-aes")] Some(password) => {
+                let encrypted = aes_encrypt(password, &compressed[..])?;
+                let mut extra = Vec::new();
+                extra.extend_from_slice(&AES_EXTRA_FIELD.to_le_bytes());
+                extra.extend_from_slice(&7u16.to_le_bytes());
+                extra.extend_from_slice(&2u16.to_le_bytes()); // AE-2
+                extra.extend_from_slice(b"AE");
+                extra.push(3); // AES-256
+                extra.extend_from_slice(&method.to_le_bytes());
+                self.add_entry(NewEntry {
+                    flags:      1, // encrypted
+                    method:     METHOD_AES,
+                    crc32:      0, // AE-2 relies on the authentication code instead
+                    compressed: &encrypted[..],
+                    extra,
+                    ..entry
+                })
+            },
+            #[cfg(not(feature = "zip-aes"))] Some(_) => Err(io::Error::new(io::ErrorKind::Other, "encrypting files requires the zip-aes feature")),
+        }
+    }
+
+    fn add_entry(&mut self, entry: NewEntry) -> io::Result<()> {
+        let NewEntry { name, flags, method, crc32, uncompressed, compressed, external_attributes, mut extra } = entry;
+        if let Some(caller) = std::str::from_utf8(&name[..]).ok().and_then(|n| self.extras.remove(n.trim_end_matches('/'))) {
+            extra.extend_from_slice(&caller[..]);
+        }
+        let now = unix_now();
+        if extra_field(&extra[..], EXTENDED_TIMESTAMP).is_none() { extra.extend_from_slice(&extended_timestamp(now)[..]); }
+        if self.finished { return Err(io::Error::new(io::ErrorKind::Other, "zip archive already finished")) }
+        if name.len() > 0xFFFF { return Err(io::Error::new(io::ErrorKind::InvalidInput, "path too long for a zip archive")) }
+        // Leave room for the Zip64 extra field: up to 4 + 8 + 8 + 8 bytes in the central directory, once the offset is known
+        if extra.len() > 0xFFFF - 28 { return Err(io::Error::new(io::ErrorKind::InvalidInput, "extra fields too long for a zip archive")) }
+
+        let header_offset   = self.io.seek(SeekFrom::Current(0))?;
+        let (dos_time, dos_date) = dos_time_date(now);
+        let compressed_len  = compressed.len() as u64;
+        let zip64           = uncompressed >= 0xFFFF_FFFF || compressed_len >= 0xFFFF_FFFF;
+        let flags           = flags | if std::str::from_utf8(&name[..]).map_or(false, |n| !n.is_ascii()) { FLAG_UTF8 } else { 0 };
+        let version_needed : u16 = match method {
+            METHOD_ZSTD     => 63,
+            METHOD_AES      => 51,
+            METHOD_BZIP2    => 46,
+            _ if zip64      => 45,
+            _               => 20,
+        };
+
+        let mut h = Vec::with_capacity(30 + name.len() + 20);
+        h.extend_from_slice(&LOCAL_FILE_HEADER_SIGNATURE.to_le_bytes());
+        h.extend_from_slice(&version_needed.to_le_bytes());
+        h.extend_from_slice(&flags.to_le_bytes());
+        h.extend_from_slice(&method.to_le_bytes());
+        h.extend_from_slice(&dos_time.to_le_bytes());
+        h.extend_from_slice(&dos_date.to_le_bytes());
+        h.extend_from_slice(&crc32.to_le_bytes());
+        h.extend_from_slice(&clamp32(if zip64 { !0 } else { compressed_len }).to_le_bytes());
+        h.extend_from_slice(&clamp32(if zip64 { !0 } else { uncompressed   }).to_le_bytes());
+        h.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        h.extend_from_slice(&((if zip64 { 20 } else { 0 } + extra.len()) as u16).to_le_bytes());
+        h.extend_from_slice(&name[..]);
+        if zip64 {
+            h.extend_from_slice(&1u16.to_le_bytes()); // Zip64 extended information
+            h.extend_from_slice(&16u16.to_le_bytes());
+            h.extend_from_slice(&uncompressed.to_le_bytes());
+            h.extend_from_slice(&compressed_len.to_le_bytes());
+        }
+        h.extend_from_slice(&extra[..]);
+        self.io.write_all(&h[..])?;
+        self.io.write_all(compressed)?;
+
+        self.entries.push(CentralEntry {
+            name,
+            flags,
+            method,
+            version_needed,
+            dos_time,
+            dos_date,
+            crc32,
+            compressed: compressed_len,
+            uncompressed,
+            header_offset,
+            external_attributes,
+            extra,
+        });
+        Ok(())
+    }
+
+    /// Write the central directory.  Also done automatically on drop, ignoring errors.
+    pub fn finish(&mut self) -> io::Result<()> {
+        if self.finished { return Ok(()) }
+        self.finished = true;
+
+        let cd_start = self.io.seek(SeekFrom::Current(0))?;
+        for e in self.entries.iter() {
+            let mut zip64 = Vec::new();
+            if e.uncompressed   >= 0xFFFF_FFFF { zip64.extend_from_slice(&e.uncompressed .to_le_bytes()); }
+            if e.compressed     >= 0xFFFF_FFFF { zip64.extend_from_slice(&e.compressed   .to_le_bytes()); }
+            if e.header_offset  >= 0xFFFF_FFFF { zip64.extend_from_slice(&e.header_offset.to_le_bytes()); }
+            let extra_len = if zip64.is_empty() { 0 } else { 4 + zip64.len() } + e.extra.len();
+            let path = std::str::from_utf8(&e.name[..]).map(|n| n.trim_end_matches('/')).unwrap_or("");
+            let comment = self.comments.get(path).map_or(&[][..], |c| &c[..]);
+
+            let mut h = Vec::with_capacity(46 + e.name.len() + extra_len + comment.len());
+            h.extend_from_slice(&CENTRAL_DIRECTORY_HEADER_SIGNATURE.to_le_bytes());
+            h.extend_from_slice(&VERSION_MADE_BY.to_le_bytes());
+            h.extend_from_slice(&e.version_needed.max(if zip64.is_empty() { 0 } else { 45 }).to_le_bytes());
+            h.extend_from_slice(&e.flags.to_le_bytes());
+            h.extend_from_slice(&e.method.to_le_bytes());
+            h.extend_from_slice(&e.dos_time.to_le_bytes());
+            h.extend_from_slice(&e.dos_date.to_le_bytes());
+            h.extend_from_slice(&e.crc32.to_le_bytes());
+            h.extend_from_slice(&clamp32(e.compressed).to_le_bytes());
+            h.extend_from_slice(&clamp32(e.uncompressed).to_le_bytes());
+            h.extend_from_slice(&(e.name.len() as u16).to_le_bytes());
+            h.extend_from_slice(&(extra_len as u16).to_le_bytes());
+            h.extend_from_slice(&(comment.len() as u16).to_le_bytes());
+            h.extend_from_slice(&0u16.to_le_bytes()); // disk number start
+            h.extend_from_slice(&0u16.to_le_bytes()); // internal attributes
+            h.extend_from_slice(&e.external_attributes.to_le_bytes());
+            h.extend_from_slice(&clamp32(e.header_offset).to_le_bytes());
+            h.extend_from_slice(&e.name[..]);
+            if !zip64.is_empty() {
+                h.extend_from_slice(&1u16.to_le_bytes()); // Zip64 extended information
+                h.extend_from_slice(&(zip64.len() as u16).to_le_bytes());
+                h.extend_from_slice(&zip64[..]);
+            }
+            h.extend_from_slice(&e.extra[..]);
+            h.extend_from_slice(comment);
+            self.io.write_all(&h[..])?;
+        }
+        let cd_end  = self.io.seek(SeekFrom::Current(0))?;
+        let cd_size = cd_end - cd_start;
+        let entries = self.entries.len() as u64;
+
+        let mut h = Vec::new();
+        if entries >= 0xFFFF || cd_size >= 0xFFFF_FFFF || cd_start >= 0xFFFF_FFFF {
+            h.extend_from_slice(&ZIP64_EOCD_SIGNATURE.to_le_bytes());
+            h.extend_from_slice(&44u64.to_le_bytes()); // size of remaining record
+            h.extend_from_slice(&VERSION_MADE_BY.to_le_bytes());
+            h.extend_from_slice(&45u16.to_le_bytes()); // version needed
+            h.extend_from_slice(&0u32.to_le_bytes()); // this disk
+            h.extend_from_slice(&0u32.to_le_bytes()); // central directory disk
+            h.extend_from_slice(&entries.to_le_bytes()); // entries on this disk
+            h.extend_from_slice(&entries.to_le_bytes()); // entries total
+            h.extend_from_slice(&cd_size.to_le_bytes());
+            h.extend_from_slice(&cd_start.to_le_bytes());
+
+            h.extend_from_slice(&ZIP64_EOCD_LOCATOR_SIGNATURE.to_le_bytes());
+            h.extend_from_slice(&0u32.to_le_bytes()); // disk with zip64 EOCD
+            h.extend_from_slice(&cd_end.to_le_bytes());
+            h.extend_from_slice(&1u32.to_le_bytes()); // total disks
+        }
+        h.extend_from_slice(&EOCD_SIGNATURE.to_le_bytes());
+        h.extend_from_slice(&0u16.to_le_bytes()); // this disk
+        h.extend_from_slice(&0u16.to_le_bytes()); // central directory disk
+        h.extend_from_slice(&(entries.min(0xFFFF) as u16).to_le_bytes());
+        h.extend_from_slice(&(entries.min(0xFFFF) as u16).to_le_bytes());
+        h.extend_from_slice(&clamp32(cd_size).to_le_bytes());
+        h.extend_from_slice(&clamp32(cd_start).to_le_bytes());
+        h.extend_from_slice(&(self.comment.len() as u16).to_le_bytes());
+        h.extend_from_slice(&self.comment[..]);
+        self.io.write_all(&h[..])?;
+        self.io.flush()
+    }
+}
+
+impl<IO: Write + Seek> Drop for ZipWriter<IO> {
+    fn drop(&mut self) { let _ = self.finish(); }
+}
+
+fn clamp32(n: u64) -> u32 { n.min(0xFFFF_FFFF) as u32 }
+
+fn compress(data: &[u8], compression: ZipCompression) -> io::Result<(u16, Cow<'_, [u8]>)> {
+    match compression {
+        ZipCompression::Stored => Ok((METHOD_STORED, Cow::Borrowed(data))),
+        #[cfg(feature = "zip-deflate")] ZipCompression::Deflated => {
+            let mut e = flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+            e.write_all(data)?;
+            Ok((METHOD_DEFLATED, Cow::Owned(e.finish()?)))
+        },
+        #[cfg(feature = "zip-bzip2")] ZipCompression::Bzip2 => {
+            let mut e = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::Default);
+            e.write_all(data)?;
+            Ok((METHOD_BZIP2, Cow::Owned(e.finish()?)))
+        },
+        #[cfg(feature = "zip-zstd")] ZipCompression::Zstd => {
+            Ok((METHOD_ZSTD, Cow::Owned(zstd::stream::encode_all(data, 0)?)))
+        },
+    }
+}
+
+/// The current time in seconds since the Unix epoch (UTC), or [None] without the `zip-time` feature.
+#[cfg(feature = "zip-time")] fn unix_now() -> Option<u64> {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    SystemTime::now().duration_since(UNIX_EPOCH).ok().map(|since_epoch| since_epoch.as_secs())
+}
+#[cfg(not(feature = "zip-time"))] fn unix_now() -> Option<u64> { None }
+
+/// `unix` (see [unix_now]) as MS-DOS (time, date), or 1980-01-01 00:00:00 if it's unknown or out of range.
+///
+/// MS-DOS timestamps are conventionally local time, but this writes UTC: std can't tell us the local UTC offset.
+/// [extended_timestamp] records the unambiguous time for readers that understand it.
+fn dos_time_date(unix: Option<u64>) -> (u16, u16) {
+    if let Some(secs) = unix {
+        let (y, m, d) = civil_from_days((secs / 86400) as i64);
+        let tod = secs % 86400;
+        if y >= 1980 && y <= 2107 {
+            let time = (tod / 3600) << 11 | (tod / 60 % 60) << 5 | ((tod % 60) / 2);
+            let date = ((y - 1980) as u64) << 9 | (m as u64) << 5 | d as u64;
+            return (time as u16, date as u16);
+        }
+    }
+    (0, (1 << 5) | 1)
+}
+
+/// An Info-ZIP extended timestamp extra field (0x5455) with just the modification time `unix` (see [unix_now]), or
+/// nothing if it's unknown or doesn't fit the field's signed 32 bit seconds.
+fn extended_timestamp(unix: Option<u64>) -> Vec<u8> {
+    let mtime = match unix { Some(secs) if secs <= std::i32::MAX as u64 => secs as u32, _ => return Vec::new() };
+    let mut extra = Vec::with_capacity(9);
+    extra.extend_from_slice(&EXTENDED_TIMESTAMP.to_le_bytes());
+    extra.extend_from_slice(&5u16.to_le_bytes());
+    extra.push(1); // flags: modification time present
+    extra.extend_from_slice(&mtime.to_le_bytes());
+    extra
+}
+
+/// Days since 1970-01-01 -> (year, month, day), per <http://howardhinnant.github.io/date_algorithms.html#civil_from_days>
+fn civil_from_days(z: i64) -> (i64, u32, u32) {
+    let z = z + 719468;
+    let era = if z >= 0 { z } else { z - 146096 } / 146097;
+    let doe = (z - era * 146097) as u64;
+    let yoe = (doe - doe/1460 + doe/36524 - doe/146096) / 365;
+    let y = yoe as i64 + era * 400;
+    let doy = doe - (365*yoe + yoe/4 - yoe/100);
+    let mp = (5*doy + 2)/153;
+    let d = (doy - (153*mp+2)/5 + 1) as u32;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    (if m <= 2 { y + 1 } else { y }, m, d)
+}